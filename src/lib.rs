@@ -1,9 +1,14 @@
 #[cfg(feature="datasize")]
 use datasize::DataSize;
 
-use log::{error, debug};
+use log::debug;
 
-#[derive(Clone, Debug)]
+#[cfg(feature="serde")]
+pub mod serde_grid;
+#[cfg(feature="serde")]
+pub mod serde_sparse;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 #[cfg_attr(feature="datasize", derive(DataSize))]
 #[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature="rkyv", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
@@ -12,15 +17,40 @@ pub enum Cell<T> {
     Occupied { value: T, colspan: u32, rowspan: u32 },
     Shadowed { col: u32, row: u32 }
 }
+impl<T> Cell<T> {
+    pub fn is_empty(&self) -> bool {
+        matches!(*self, Cell::Empty)
+    }
+    pub fn is_occupied(&self) -> bool {
+        matches!(*self, Cell::Occupied { .. })
+    }
+    pub fn is_shadowed(&self) -> bool {
+        matches!(*self, Cell::Shadowed { .. })
+    }
+}
 
 #[derive(Debug)]
 pub enum Error {
     Shadowed { col: u32, row: u32 },
+    OutOfBounds { row: u32, col: u32 },
+    Overlap { col: u32, row: u32 },
+    SplitSpan { col: u32, row: u32 },
+    Empty { col: u32, row: u32 },
+    /// The cell at `row`, `col` would have a zero rowspan or colspan.
+    InvalidSpan { col: u32, row: u32 },
+    /// `rows` x `cols` cells do not fit in the address space.
+    TooLarge { rows: u32, cols: u32 },
 }
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match *self {
-            Error::Shadowed { col, row } => write!(f, "Shadowd by cell at row {row}, col {col}")
+            Error::Shadowed { col, row } => write!(f, "Shadowd by cell at row {row}, col {col}"),
+            Error::OutOfBounds { row, col } => write!(f, "cell at row {row}, col {col} out of bounds"),
+            Error::Overlap { col, row } => write!(f, "overlaps another cell at row {row}, col {col}"),
+            Error::SplitSpan { col, row } => write!(f, "would split the span of cell at row {row}, col {col}"),
+            Error::Empty { col, row } => write!(f, "no cell at row {row}, col {col}"),
+            Error::InvalidSpan { col, row } => write!(f, "cell at row {row}, col {col} has an empty span"),
+            Error::TooLarge { rows, cols } => write!(f, "table of {rows} rows and {cols} columns is too large"),
         }
     }
 }
@@ -28,55 +58,339 @@ impl std::error::Error for Error {
 
 }
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
+pub enum ValidationError {
+    /// The shadow at `row`, `col` points to an anchor outside the table.
+    ShadowOutOfBounds { row: u32, col: u32, anchor_row: u32, anchor_col: u32 },
+    /// The shadow at `row`, `col` points to a cell that is not occupied.
+    ShadowWithoutAnchor { row: u32, col: u32, anchor_row: u32, anchor_col: u32 },
+    /// The shadow at `row`, `col` points to an anchor whose span does not cover it.
+    ShadowNotCovered { row: u32, col: u32, anchor_row: u32, anchor_col: u32 },
+    /// The occupied cell at `row`, `col` has a zero rowspan or colspan.
+    EmptySpan { row: u32, col: u32 },
+    /// The span of the occupied cell at `row`, `col` extends past the table.
+    SpanOutOfBounds { row: u32, col: u32 },
+    /// The coordinate `row`, `col` is covered by the anchor but is not a shadow pointing to it.
+    MissingShadow { row: u32, col: u32, anchor_row: u32, anchor_col: u32 },
+    /// The storage holds `len` cells, which does not match `rows` x `cols`.
+    CellCount { rows: u32, cols: u32, len: usize },
+}
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            ValidationError::ShadowOutOfBounds { row, col, anchor_row, anchor_col } => write!(f, "shadow at row {row}, col {col} points to row {anchor_row}, col {anchor_col} out of bounds"),
+            ValidationError::ShadowWithoutAnchor { row, col, anchor_row, anchor_col } => write!(f, "shadow at row {row}, col {col} points to unoccupied cell at row {anchor_row}, col {anchor_col}"),
+            ValidationError::ShadowNotCovered { row, col, anchor_row, anchor_col } => write!(f, "shadow at row {row}, col {col} is not covered by cell at row {anchor_row}, col {anchor_col}"),
+            ValidationError::EmptySpan { row, col } => write!(f, "cell at row {row}, col {col} has an empty span"),
+            ValidationError::SpanOutOfBounds { row, col } => write!(f, "span of cell at row {row}, col {col} is out of bounds"),
+            ValidationError::MissingShadow { row, col, anchor_row, anchor_col } => write!(f, "row {row}, col {col} is not shadowed by cell at row {anchor_row}, col {anchor_col}"),
+            ValidationError::CellCount { rows, cols, len } => write!(f, "{len} cells do not fill {rows} rows and {cols} columns"),
+        }
+    }
+}
+impl std::error::Error for ValidationError {
+
+}
+
+/// Why a coordinate holds no value of its own.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CellError {
+    Empty,
+    /// The coordinate is covered by the span of the cell at `anchor`, given as `(row, col)`.
+    Shadowed { anchor: (u32, u32) },
+    OutOfBounds,
+}
+impl std::fmt::Display for CellError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            CellError::Empty => write!(f, "cell is empty"),
+            CellError::Shadowed { anchor: (row, col) } => write!(f, "cell is shadowed by cell at row {row}, col {col}"),
+            CellError::OutOfBounds => write!(f, "cell out of bounds"),
+        }
+    }
+}
+impl std::error::Error for CellError {
+
+}
+
+/// How `format_html_styled` renders `Cell::Empty`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum EmptyCellStyle {
+    /// An empty `<td></td>`.
+    Empty,
+    /// No element at all.
+    /// The browser shifts the following cells left, so columns only line up
+    /// if the surrounding spans account for the missing cells.
+    Omit,
+    /// A cell holding the given HTML, e.g. `&nbsp;`. It is not escaped.
+    Placeholder(String),
+}
+
+/// Element names used by `format_html_with_tags`. The default reproduces `format_html`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HtmlTags {
+    /// The outer wrapper, left out if `None`.
+    pub table: Option<String>,
+    /// Wraps the header rows, left out if `None`.
+    pub thead: Option<String>,
+    /// Wraps the body rows, left out if `None`.
+    pub tbody: Option<String>,
+    pub tr: String,
+    pub td: String,
+    /// Used for header rows and for cells marked with `set_header`.
+    pub th: String,
+}
+impl Default for HtmlTags {
+    fn default() -> Self {
+        HtmlTags {
+            table: Some("table".into()),
+            thead: Some("thead".into()),
+            tbody: Some("tbody".into()),
+            tr: "tr".into(),
+            td: "td".into(),
+            th: "th".into(),
+        }
+    }
+}
+#[derive(Copy, Clone)]
+struct HtmlStyle<'a> {
+    tags: &'a HtmlTags,
+    empty: &'a EmptyCellStyle,
+}
+
+/// Horizontal alignment of a column, used by `format_box`, `format_markdown` and the `format_html` family.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature="datasize", derive(DataSize))]
+#[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature="rkyv", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
+pub enum Alignment {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature="datasize", derive(DataSize))]
 #[cfg_attr(feature="serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature="rkyv", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize))]
 pub struct Table<T> {
     num_cols: u32,
     num_rows: u32,
-    cells: Vec<Cell<T>>
+    cells: Vec<Cell<T>>,
+    /// Columns that are not `Alignment::Left`, sorted by column.
+    /// Always serialized so formats without field names stay readable; `default` lets tables saved without it load.
+    #[cfg_attr(feature="serde", serde(default))]
+    alignments: Vec<(u32, Alignment)>,
+    /// Anchors rendered as header cells, sorted by `(row, col)`.
+    /// Always serialized like `alignments`; `default` lets tables saved without it load.
+    #[cfg_attr(feature="serde", serde(default))]
+    headers: Vec<(u32, u32)>,
 }
 use std::mem::replace;
 use std::ops::{Index, IndexMut};
 use std::fmt;
-use std::collections::HashSet;
+use std::io;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
 
 impl<T> Table<T> {
     pub fn new() -> Self {
-        Table { num_cols: 0, num_rows: 0, cells: vec![] }
+        Table { num_cols: 0, num_rows: 0, cells: vec![], alignments: vec![], headers: vec![] }
+    }
+    /// Starts out as a 0x0 table like `new`, but with room for `rows` x `cols` cells,
+    /// so growing it through `set_cell` does not reallocate.
+    /// Unlike `empty`, no cells are created.
+    pub fn with_capacity(rows: u32, cols: u32) -> Self {
+        Table { num_cols: 0, num_rows: 0, cells: Vec::with_capacity(rows as usize * cols as usize), alignments: vec![], headers: vec![] }
     }
+    /// Panics if `rows` x `columns` cells do not fit in `usize`.
     pub fn empty(rows: u32, columns: u32) -> Self {
-        let cells = std::iter::from_fn(|| Some(Cell::Empty)).take(rows as usize * columns as usize).collect();
-        Table { num_cols: columns, num_rows: rows, cells }
+        let len = area(rows, columns).unwrap_or_else(|| panic!("{}", Error::TooLarge { rows, cols: columns }));
+        let cells = std::iter::from_fn(|| Some(Cell::Empty)).take(len).collect();
+        Table { num_cols: columns, num_rows: rows, cells, alignments: vec![], headers: vec![] }
+    }
+    /// Like `empty`, but fails with `Error::TooLarge` instead of aborting if the cells cannot be allocated.
+    #[cfg(feature="serde")]
+    pub(crate) fn try_empty(rows: u32, cols: u32) -> Result<Self, Error> {
+        let len = area(rows, cols).ok_or(Error::TooLarge { rows, cols })?;
+        let mut cells = Vec::new();
+        cells.try_reserve_exact(len).map_err(|_| Error::TooLarge { rows, cols })?;
+        cells.extend(std::iter::from_fn(|| Some(Cell::Empty)).take(len));
+        Ok(Table { num_cols: cols, num_rows: rows, cells, alignments: vec![], headers: vec![] })
+    }
+    /// Builds a table of 1x1 cells, padding short rows with `Cell::Empty`.
+    /// An empty `rows` gives the same 0x0 table as `Table::new()`.
+    pub fn from_rows(rows: Vec<Vec<Option<T>>>) -> Self {
+        let num_cols = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        let num_rows = rows.len();
+        let mut cells = Vec::with_capacity(num_cols * num_rows);
+        for row in rows {
+            let len = row.len();
+            cells.extend(row.into_iter().map(|value| match value {
+                Some(value) => Cell::Occupied { value, colspan: 1, rowspan: 1 },
+                None => Cell::Empty
+            }));
+            cells.extend(std::iter::from_fn(|| Some(Cell::Empty)).take(num_cols - len));
+        }
+        Table { num_cols: num_cols as u32, num_rows: num_rows as u32, cells, alignments: vec![], headers: vec![] }
+    }
+    pub fn builder() -> RowBuilder<T> {
+        RowBuilder { rows: vec![] }
+    }
+    pub fn view(&self) -> TableView<'_, T> {
+        TableView { table: self }
     }
     pub fn size(&self) -> (u32, u32) {
         (self.num_rows, self.num_cols)
     }
-    pub fn set_cell(&mut self, value: T, row: u32, col: u32, rowspan: u32, colspan: u32) -> Result<Option<T>, Error> {
-        let cols = col + colspan;
-        let rows = row + rowspan;
-        if cols > self.num_cols {
-            let cells = replace(&mut self.cells, Vec::with_capacity((cols as usize) * (self.num_rows as usize).max(row as usize + 1)));
-            let mut cells = cells.into_iter();
-            for _ in 0 .. self.num_rows {
-                self.cells.extend(cells.by_ref().take(self.num_cols as usize));
-                self.cells.extend(std::iter::from_fn(|| Some(Cell::Empty)).take(cols as usize - self.num_cols as usize));
+    pub fn num_rows(&self) -> u32 {
+        self.num_rows
+    }
+    pub fn num_cols(&self) -> u32 {
+        self.num_cols
+    }
+    /// Index of the bottom row, `None` if there are no rows.
+    pub fn last_row(&self) -> Option<u32> {
+        self.num_rows.checked_sub(1)
+    }
+    /// Index of the rightmost column, `None` if there are no columns.
+    pub fn last_col(&self) -> Option<u32> {
+        self.num_cols.checked_sub(1)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.num_rows == 0 || self.num_cols == 0
+    }
+    /// Sets how renderers align column `col`. The alignment moves along with the column
+    /// when columns are inserted, deleted or swapped.
+    pub fn set_column_alignment(&mut self, col: u32, align: Alignment) {
+        match (self.alignments.binary_search_by_key(&col, |&(c, _)| c), align) {
+            (Ok(i), Alignment::Left) => { self.alignments.remove(i); }
+            (Ok(i), align) => self.alignments[i].1 = align,
+            (Err(_), Alignment::Left) => {}
+            (Err(i), align) => self.alignments.insert(i, (col, align)),
+        }
+    }
+    /// `Alignment::Left` unless set otherwise with `set_column_alignment`.
+    pub fn column_alignment(&self, col: u32) -> Alignment {
+        match self.alignments.binary_search_by_key(&col, |&(c, _)| c) {
+            Ok(i) => self.alignments[i].1,
+            Err(_) => Alignment::Left
+        }
+    }
+    /// Marks the occupied cell at `row`, `col` as a header, so the `format_html` family renders it as `<th>`.
+    /// The flag stays with the anchor when rows and columns move and is dropped when the cell is removed.
+    pub fn set_header(&mut self, row: u32, col: u32, header: bool) -> Result<(), Error> {
+        match self.get_cell(row, col) {
+            Some(Cell::Occupied { .. }) => {}
+            Some(&Cell::Shadowed { col, row }) => return Err(Error::Shadowed { col, row }),
+            Some(Cell::Empty) => return Err(Error::Empty { col, row }),
+            None => return Err(Error::OutOfBounds { row, col }),
+        }
+        match (self.headers.binary_search(&(row, col)), header) {
+            (Ok(i), false) => { self.headers.remove(i); }
+            (Err(i), true) => self.headers.insert(i, (row, col)),
+            _ => {}
+        }
+        Ok(())
+    }
+    pub fn is_header(&self, row: u32, col: u32) -> bool {
+        self.headers.binary_search(&(row, col)).is_ok()
+    }
+    /// Moves the header flag of each anchor to `f(row, col)` and drops flags no longer sitting on an anchor.
+    fn remap_headers(&mut self, f: impl Fn(u32, u32) -> Option<(u32, u32)>) {
+        let headers = std::mem::take(&mut self.headers);
+        self.headers = headers.into_iter().filter_map(|(row, col)| f(row, col))
+            .filter(|&(row, col)| matches!(self.get_cell(row, col), Some(Cell::Occupied { .. }))).collect();
+        self.headers.sort_unstable();
+        self.headers.dedup();
+    }
+    fn drop_header(&mut self, row: u32, col: u32) {
+        if let Ok(i) = self.headers.binary_search(&(row, col)) {
+            self.headers.remove(i);
+        }
+    }
+    /// Moves the alignment of each column `col` to `f(col)`, dropping it if that is `None`.
+    fn remap_alignments(&mut self, f: impl Fn(u32) -> Option<u32>) {
+        self.alignments = self.alignments.iter().filter_map(|&(col, align)| f(col).map(|col| (col, align))).collect();
+        self.alignments.sort_unstable_by_key(|&(col, _)| col);
+    }
+    /// Resets every cell to `Cell::Empty`, keeping the dimensions and the allocated capacity.
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            *cell = Cell::Empty;
+        }
+        self.headers.clear();
+    }
+    /// Turns every `Cell::Empty` into a 1x1 clone of `value`.
+    /// Shadowed coordinates belong to real spans and are left as they are.
+    pub fn fill_empty(&mut self, value: T) where T: Clone {
+        for cell in self.cells.iter_mut() {
+            if let Cell::Empty = *cell {
+                *cell = Cell::Occupied { value: value.clone(), colspan: 1, rowspan: 1 };
             }
-            self.num_cols = cols;
-            
-            assert_eq!(self.num_cols as usize * self.num_rows as usize, self.cells.len());
         }
-        if rows > self.num_rows {
-            self.cells.extend(std::iter::from_fn(|| Some(Cell::Empty)).take((rows - self.num_rows) as usize * self.num_cols as usize));
-            self.num_rows = rows;
-
-            assert_eq!(self.num_cols as usize * self.num_rows as usize, self.cells.len());
+    }
+    /// Sets every coordinate in the `rows` x `cols` rectangle at `row`, `col` to a 1x1 clone of `value`,
+    /// growing the table if needed. Spans reaching into the rectangle are removed entirely first.
+    /// Fails like `set_cell` if the rectangle reaches past `u32::MAX` or the grown table is too large.
+    pub fn fill_region(&mut self, row: u32, col: u32, rows: u32, cols: u32, value: T) -> Result<(), Error> where T: Clone {
+        if rows == 0 || cols == 0 {
+            return Ok(());
+        }
+        let (Some(end_row), Some(end_col)) = (row.checked_add(rows), col.checked_add(cols)) else {
+            return Err(Error::OutOfBounds { row, col });
+        };
+        if area(end_row.max(self.num_rows), end_col.max(self.num_cols)).is_none() {
+            return Err(Error::TooLarge { rows: end_row.max(self.num_rows), cols: end_col.max(self.num_cols) });
+        }
+        self.grow_to(end_row, end_col);
+        for r in row .. end_row {
+            for c in col .. end_col {
+                self.remove_cell(r, c);
+            }
+        }
+        for r in row .. end_row {
+            for c in col .. end_col {
+                self.set(r, c, Cell::Occupied { value: value.clone(), colspan: 1, rowspan: 1 });
+            }
+        }
+        Ok(())
+    }
+    pub fn occupied_count(&self) -> usize {
+        self.cells.iter().filter(|cell| cell.is_occupied()).count()
+    }
+    /// Counts the cells of each kind in one pass.
+    pub fn cell_stats(&self) -> CellStats {
+        self.cells.iter().fold(CellStats::default(), |mut stats, cell| {
+            match *cell {
+                Cell::Empty => stats.empty += 1,
+                Cell::Occupied { .. } => stats.occupied += 1,
+                Cell::Shadowed { .. } => stats.shadowed += 1,
+            }
+            stats
+        })
+    }
+    pub fn set_cell(&mut self, value: T, row: u32, col: u32, rowspan: u32, colspan: u32) -> Result<Option<T>, Error> {
+        if rowspan == 0 || colspan == 0 {
+            return Err(Error::InvalidSpan { col, row });
+        }
+        if let Some(&Cell::Shadowed { col, row }) = self.get_cell(row, col) {
+            return Err(Error::Shadowed { col, row });
         }
+        let (Some(rows), Some(cols)) = (row.checked_add(rowspan), col.checked_add(colspan)) else {
+            return Err(Error::OutOfBounds { row, col });
+        };
+        if area(rows.max(self.num_rows), cols.max(self.num_cols)).is_none() {
+            return Err(Error::TooLarge { rows: rows.max(self.num_rows), cols: cols.max(self.num_cols) });
+        }
+        self.grow_to(rows, cols);
         let new_cell = Cell::Occupied { value, colspan, rowspan };
         let old_cell = self.replace(row, col, new_cell);
-        match old_cell {
+        let old_value = match old_cell {
             Cell::Occupied { value: cell_value, colspan: old_colspan, rowspan: old_rowspan } => {
+                // clear the whole old span before stamping the new one,
+                // so nothing outside the new span keeps pointing here
                 for r in row + 1 .. row + old_rowspan {
                     self.set(r, col, Cell::Empty);
                 }
@@ -85,157 +399,3565 @@ impl<T> Table<T> {
                         self.set(r, c, Cell::Empty);
                     }
                 }
+                Some(cell_value)
+            }
+            Cell::Empty => None,
+            Cell::Shadowed { .. } => unreachable!(),
+        };
+        self.fill_span(row, col, rowspan, colspan);
+        if !self.headers.is_empty() && (rowspan, colspan) != (1, 1) {
+            // anchors clobbered by the new span lose their header flag
+            self.remap_headers(|row, col| Some((row, col)));
+        }
+        Ok(old_value)
+    }
+    /// Shadows everything but the anchor in the given span.
+    fn fill_span(&mut self, row: u32, col: u32, rowspan: u32, colspan: u32) {
+        for r in row + 1 .. row + rowspan {
+            self.set(r, col, Cell::Shadowed { col, row });
+        }
+        for c in col + 1 .. col + colspan {
+            for r in row .. row + rowspan {
+                self.set(r, c, Cell::Shadowed { col, row });
+            }
+        }
+    }
+    /// Appends a row of 1x1 cells below the table, widening it if `values` is longer than a row.
+    pub fn push_row(&mut self, values: Vec<Option<T>>) {
+        if values.len() as u32 > self.num_cols {
+            self.set_num_cols(values.len() as u32, self.num_rows + 1);
+        }
+        let len = values.len();
+        self.cells.extend(values.into_iter().map(|value| match value {
+            Some(value) => Cell::Occupied { value, colspan: 1, rowspan: 1 },
+            None => Cell::Empty
+        }));
+        self.cells.extend(std::iter::from_fn(|| Some(Cell::Empty)).take(self.num_cols as usize - len));
+        self.num_rows += 1;
+    }
+    /// Appends the rows of `other` below this table.
+    /// The narrower of the two tables is padded with `Cell::Empty` to the wider one.
+    /// Column alignments of `other` are taken over for columns that are left-aligned here.
+    pub fn append_rows(&mut self, other: Table<T>) -> Result<(), Error> {
+        let cols = self.num_cols.max(other.num_cols);
+        if cols > self.num_cols {
+            self.set_num_cols(cols, self.num_rows + other.num_rows);
+        }
+        let offset = self.num_rows;
+        self.cells.reserve(cols as usize * other.num_rows as usize);
+        let mut cells = other.cells.into_iter().map(|cell| match cell {
+            Cell::Shadowed { col, row } => Cell::Shadowed { col, row: row + offset },
+            cell => cell
+        });
+        for _ in 0 .. other.num_rows {
+            self.cells.extend(cells.by_ref().take(other.num_cols as usize));
+            self.cells.extend(std::iter::from_fn(|| Some(Cell::Empty)).take((cols - other.num_cols) as usize));
+        }
+        self.num_rows += other.num_rows;
+        self.headers.extend(other.headers.into_iter().map(|(row, col)| (row + offset, col)));
+        for (col, align) in other.alignments {
+            if self.column_alignment(col) == Alignment::Left {
+                self.set_column_alignment(col, align);
+            }
+        }
+
+        assert_eq!(self.num_cols as usize * self.num_rows as usize, self.cells.len());
+        Ok(())
+    }
+    /// Places the columns of `other` to the right of this table.
+    /// The shorter of the two tables is padded with `Cell::Empty` to the taller one.
+    pub fn append_columns(&mut self, other: Table<T>) -> Result<(), Error> {
+        let rows = self.num_rows.max(other.num_rows);
+        let offset = self.num_cols;
+        let cols = self.num_cols + other.num_cols;
+        let cells = replace(&mut self.cells, Vec::with_capacity(rows as usize * cols as usize));
+        let mut left = cells.into_iter();
+        let mut right = other.cells.into_iter().map(|cell| match cell {
+            Cell::Shadowed { col, row } => Cell::Shadowed { col: col + offset, row },
+            cell => cell
+        });
+        for row in 0 .. rows {
+            if row < self.num_rows {
+                self.cells.extend(left.by_ref().take(self.num_cols as usize));
+            } else {
+                self.cells.extend(std::iter::from_fn(|| Some(Cell::Empty)).take(self.num_cols as usize));
+            }
+            if row < other.num_rows {
+                self.cells.extend(right.by_ref().take(other.num_cols as usize));
+            } else {
+                self.cells.extend(std::iter::from_fn(|| Some(Cell::Empty)).take(other.num_cols as usize));
+            }
+        }
+        self.num_rows = rows;
+        self.num_cols = cols;
+        self.alignments.extend(other.alignments.into_iter().map(|(col, align)| (col + offset, align)));
+        self.headers.extend(other.headers.into_iter().map(|(row, col)| (row, col + offset)));
+        self.headers.sort_unstable();
 
-                for r in row + 1 .. row + rowspan {
-                    self.set(r, col, Cell::Shadowed { col, row });
+        assert_eq!(self.num_cols as usize * self.num_rows as usize, self.cells.len());
+        Ok(())
+    }
+    pub fn try_set_cell_in_bounds(&mut self, value: T, row: u32, col: u32, rowspan: u32, colspan: u32) -> Result<Option<T>, Error> {
+        if rowspan == 0 || colspan == 0 {
+            return Err(Error::InvalidSpan { col, row });
+        }
+        if !self.span_in_bounds(row, col, rowspan, colspan) {
+            return Err(Error::OutOfBounds { row, col });
+        }
+        self.set_cell(value, row, col, rowspan, colspan)
+    }
+    /// Places a 1x1 cell without growing the table, returning the previous value if there was one.
+    pub fn try_set_value(&mut self, row: u32, col: u32, value: T) -> Result<Option<T>, Error> {
+        self.try_set_cell_in_bounds(value, row, col, 1, 1)
+    }
+    /// Like `set_cell`, but refuses to clobber cells belonging to another anchor inside the new span.
+    pub fn set_cell_checked(&mut self, value: T, row: u32, col: u32, rowspan: u32, colspan: u32) -> Result<Option<T>, Error> {
+        if rowspan == 0 || colspan == 0 {
+            return Err(Error::InvalidSpan { col, row });
+        }
+        // an overflowing span end is left for `set_cell` to report
+        for r in row .. row.saturating_add(rowspan).min(self.num_rows) {
+            for c in col .. col.saturating_add(colspan).min(self.num_cols) {
+                match self[(r, c)] {
+                    _ if (r, c) == (row, col) => {}
+                    Cell::Empty => {}
+                    Cell::Shadowed { col: anchor_col, row: anchor_row } if (anchor_row, anchor_col) == (row, col) => {}
+                    _ => return Err(Error::Overlap { col: c, row: r }),
                 }
-                for c in col .. col + colspan {
-                    for r in row .. row + rowspan {
-                        self.set(r, c, Cell::Shadowed { col, row });
+            }
+        }
+        self.set_cell(value, row, col, rowspan, colspan)
+    }
+    pub fn remove_cell(&mut self, row: u32, col: u32) -> Option<T> {
+        let (row, col) = self.anchor_of(row, col)?;
+        match self.replace(row, col, Cell::Empty) {
+            Cell::Occupied { value, colspan, rowspan } => {
+                for r in row .. row + rowspan {
+                    for c in col .. col + colspan {
+                        self.set(r, c, Cell::Empty);
                     }
                 }
-                Ok(Some(cell_value))
+                self.drop_header(row, col);
+                Some(value)
+            }
+            cell => {
+                debug!("no anchor at row={row}, col={col}");
+                self.set(row, col, cell);
+                None
             }
-            Cell::Empty => {
-                for r in row + 1 .. row + rowspan {
-                    self.set(r, col, Cell::Shadowed { col, row });
+        }
+    }
+    /// Inserts an empty row before row `at`.
+    /// Spans crossing the insertion point are extended by one row.
+    pub fn insert_row(&mut self, at: u32) {
+        assert!(at <= self.num_rows, "row {at} out of bounds");
+        let mut new_row = Vec::with_capacity(self.num_cols as usize);
+        for col in 0 .. self.num_cols {
+            match self.get_cell(at, col) {
+                Some(&Cell::Shadowed { col: anchor_col, row: anchor_row }) if anchor_row < at => {
+                    if anchor_col == col {
+                        if let Cell::Occupied { ref mut rowspan, .. } = self[(anchor_row, anchor_col)] {
+                            *rowspan += 1;
+                        }
+                    }
+                    new_row.push(Cell::Shadowed { col: anchor_col, row: anchor_row });
                 }
-                for c in col + 1 .. col + colspan {
-                    for r in row .. row + rowspan {
-                        self.set(r, c, Cell::Shadowed { col, row });
+                _ => new_row.push(Cell::Empty)
+            }
+        }
+        for cell in self.cells.iter_mut() {
+            if let Cell::Shadowed { ref mut row, .. } = *cell {
+                if *row >= at {
+                    *row += 1;
+                }
+            }
+        }
+        let index = self.cell_index(at, 0);
+        self.cells.splice(index .. index, new_row);
+        self.num_rows += 1;
+        self.remap_headers(|row, col| Some((if row >= at { row + 1 } else { row }, col)));
+    }
+    /// Removes row `at`. Spans covering the row shrink by one.
+    /// An anchor sitting on the deleted row moves down to the next row of its span,
+    /// or is dropped if its rowspan was 1.
+    pub fn delete_row(&mut self, at: u32) {
+        assert!(at < self.num_rows, "row {at} out of bounds");
+        for col in 0 .. self.num_cols {
+            match self.replace(at, col, Cell::Empty) {
+                Cell::Occupied { value, colspan, rowspan } if rowspan > 1 => {
+                    self.set(at + 1, col, Cell::Occupied { value, colspan, rowspan: rowspan - 1 });
+                }
+                Cell::Shadowed { col: anchor_col, row: anchor_row } if anchor_row < at && anchor_col == col => {
+                    if let Cell::Occupied { ref mut rowspan, .. } = self[(anchor_row, anchor_col)] {
+                        *rowspan -= 1;
                     }
                 }
-                Ok(None)
+                Cell::Occupied { .. } => self.drop_header(at, col),
+                _ => {}
+            }
+        }
+        let index = self.cell_index(at, 0);
+        self.cells.drain(index .. index + self.num_cols as usize);
+        self.num_rows -= 1;
+        for cell in self.cells.iter_mut() {
+            if let Cell::Shadowed { ref mut row, .. } = *cell {
+                if *row > at {
+                    *row -= 1;
+                }
             }
-            Cell::Shadowed { col, row } => Err(Error::Shadowed { col, row }),
         }
+        // an anchor moved off the deleted row ends up at `at` again
+        self.remap_headers(|row, col| Some((if row > at { row - 1 } else { row }, col)));
     }
-    #[inline]
-    fn cell_index(&self, row: u32, col: u32) -> usize {
-        self.num_cols as usize * row as usize + col as usize
+    /// Inserts an empty column before column `at`.
+    /// Spans crossing the insertion point are extended by one column.
+    pub fn insert_column(&mut self, at: u32) {
+        assert!(at <= self.num_cols, "column {at} out of bounds");
+        let mut new_col = Vec::with_capacity(self.num_rows as usize);
+        for row in 0 .. self.num_rows {
+            match self.get_cell(row, at) {
+                Some(&Cell::Shadowed { col: anchor_col, row: anchor_row }) if anchor_col < at => {
+                    if anchor_row == row {
+                        if let Cell::Occupied { ref mut colspan, .. } = self[(anchor_row, anchor_col)] {
+                            *colspan += 1;
+                        }
+                    }
+                    new_col.push(Cell::Shadowed { col: anchor_col, row: anchor_row });
+                }
+                _ => new_col.push(Cell::Empty)
+            }
+        }
+        for cell in self.cells.iter_mut() {
+            if let Cell::Shadowed { ref mut col, .. } = *cell {
+                if *col >= at {
+                    *col += 1;
+                }
+            }
+        }
+        let cells = replace(&mut self.cells, Vec::with_capacity((self.num_cols as usize + 1) * self.num_rows as usize));
+        let mut cells = cells.into_iter();
+        for cell in new_col {
+            self.cells.extend(cells.by_ref().take(at as usize));
+            self.cells.push(cell);
+            self.cells.extend(cells.by_ref().take((self.num_cols - at) as usize));
+        }
+        self.num_cols += 1;
+        self.remap_alignments(|col| Some(if col >= at { col + 1 } else { col }));
+        self.remap_headers(|row, col| Some((row, if col >= at { col + 1 } else { col })));
     }
-    #[inline]
-    fn set(&mut self, row: u32, col: u32, value: Cell<T>) {
-        let index = self.cell_index(row, col);
-        if let Some(cell) = self.cells.get_mut(index) {
-            *cell = value;
+    /// Removes column `at`. Spans covering the column shrink by one.
+    /// An anchor sitting on the deleted column moves right to the next column of its span,
+    /// or is dropped if its colspan was 1.
+    pub fn delete_column(&mut self, at: u32) {
+        assert!(at < self.num_cols, "column {at} out of bounds");
+        for row in 0 .. self.num_rows {
+            match self.replace(row, at, Cell::Empty) {
+                Cell::Occupied { value, colspan, rowspan } if colspan > 1 => {
+                    self.set(row, at + 1, Cell::Occupied { value, colspan: colspan - 1, rowspan });
+                }
+                Cell::Shadowed { col: anchor_col, row: anchor_row } if anchor_col < at && anchor_row == row => {
+                    if let Cell::Occupied { ref mut colspan, .. } = self[(anchor_row, anchor_col)] {
+                        *colspan -= 1;
+                    }
+                }
+                Cell::Occupied { .. } => self.drop_header(row, at),
+                _ => {}
+            }
+        }
+        let cells = replace(&mut self.cells, Vec::with_capacity((self.num_cols as usize - 1) * self.num_rows as usize));
+        self.cells.extend(cells.into_iter().enumerate().filter(|&(i, _)| i % self.num_cols as usize != at as usize).map(|(_, cell)| cell));
+        self.num_cols -= 1;
+        for cell in self.cells.iter_mut() {
+            if let Cell::Shadowed { ref mut col, .. } = *cell {
+                if *col > at {
+                    *col -= 1;
+                }
+            }
+        }
+        self.remap_alignments(|col| match col {
+            col if col < at => Some(col),
+            col if col > at => Some(col - 1),
+            _ => None
+        });
+        self.remap_headers(|row, col| Some((row, if col > at { col - 1 } else { col })));
+    }
+    /// Reshapes the table to exactly `rows` x `cols`.
+    /// Cells outside the new bounds are dropped and spans crossing the new edge are truncated.
+    pub fn resize(&mut self, rows: u32, cols: u32) {
+        if rows < self.num_rows || cols < self.num_cols {
+            for (row, col, cell) in self.cells_iter_mut() {
+                if let Cell::Occupied { ref mut rowspan, ref mut colspan, .. } = *cell {
+                    *rowspan = (*rowspan).min(rows.saturating_sub(row));
+                    *colspan = (*colspan).min(cols.saturating_sub(col));
+                }
+            }
+        }
+        if cols != self.num_cols {
+            self.set_num_cols(cols, rows);
+        }
+        if rows != self.num_rows {
+            self.set_num_rows(rows);
+        }
+    }
+    /// Bytes allocated for the cell storage, including spare capacity.
+    /// Heap memory owned by the values themselves is not included, see `total_heap_bytes`.
+    pub fn heap_bytes(&self) -> usize {
+        self.cells.capacity() * std::mem::size_of::<Cell<T>>()
+    }
+    /// `heap_bytes` plus the heap memory of every occupied value.
+    #[cfg(feature="datasize")]
+    pub fn total_heap_bytes(&self) -> usize where T: DataSize {
+        self.heap_bytes() + self.values().map(|cell| datasize::data_size(cell.value)).sum::<usize>()
+    }
+    /// Reserves storage for the table to grow by `additional_rows` and `additional_cols`
+    /// without reallocating. It only reserves, the dimensions are unchanged.
+    pub fn reserve(&mut self, additional_rows: u32, additional_cols: u32) {
+        let (rows, cols) = (self.num_rows.saturating_add(additional_rows), self.num_cols.saturating_add(additional_cols));
+        let needed = area(rows, cols).unwrap_or_else(|| panic!("{}", Error::TooLarge { rows, cols }));
+        self.cells.reserve(needed - self.cells.len());
+    }
+    /// Releases spare capacity of the backing storage. The dimensions are unchanged.
+    pub fn shrink_to_fit(&mut self) {
+        self.cells.shrink_to_fit();
+    }
+    /// Drops trailing rows and columns that contain nothing but `Cell::Empty`.
+    /// Leading and interior empty rows and columns are kept so coordinates stay stable.
+    pub fn trim(&mut self) {
+        let mut rows = self.num_rows;
+        while rows > 0 && self.row_is_empty(rows - 1) {
+            rows -= 1;
+        }
+        let mut cols = self.num_cols;
+        while cols > 0 && self.column_is_empty(cols - 1) {
+            cols -= 1;
+        }
+        self.resize(rows, cols);
+    }
+    /// Whether `row` contains nothing but `Cell::Empty`. Shadows count as content.
+    /// Rows past the end are empty.
+    pub fn row_is_empty(&self, row: u32) -> bool {
+        self.row(row).is_none_or(|cells| cells.iter().all(Cell::is_empty))
+    }
+    /// Whether `col` contains nothing but `Cell::Empty`. Shadows count as content.
+    /// Columns past the end are empty.
+    pub fn column_is_empty(&self, col: u32) -> bool {
+        self.column(col).is_none_or(|mut cells| cells.all(Cell::is_empty))
+    }
+    /// Enlarges the table to at least `rows` x `cols`, filling the new coordinates with `Cell::Empty`.
+    /// Unlike `resize` it never shrinks.
+    pub fn grow_to(&mut self, rows: u32, cols: u32) {
+        let (rows, cols) = (rows.max(self.num_rows), cols.max(self.num_cols));
+        if area(rows, cols).is_none() {
+            panic!("{}", Error::TooLarge { rows, cols });
+        }
+        if cols > self.num_cols {
+            self.set_num_cols(cols, rows);
+        }
+        if rows > self.num_rows {
+            self.set_num_rows(rows);
+        }
+    }
+    /// Changes the number of columns, moving the rows of the row-major storage in place.
+    /// `reserve_rows` makes room for that many rows up front.
+    fn set_num_cols(&mut self, cols: u32, reserve_rows: u32) {
+        let (old_cols, new_cols, rows) = (self.num_cols as usize, cols as usize, self.num_rows as usize);
+        if new_cols > old_cols {
+            let needed = new_cols * rows.max(reserve_rows as usize);
+            self.cells.reserve(needed.saturating_sub(self.cells.len()));
+            self.cells.extend(std::iter::from_fn(|| Some(Cell::Empty)).take((new_cols - old_cols) * rows));
+            // walk backwards so every cell is swapped onto a slot that is still empty
+            for row in (0 .. rows).rev() {
+                for col in (0 .. old_cols).rev() {
+                    self.cells.swap(row * old_cols + col, row * new_cols + col);
+                }
+            }
         } else {
-            panic!("cell row={row}, col={col} out of bounds");
+            for row in 0 .. rows {
+                for col in 0 .. new_cols {
+                    self.cells.swap(row * old_cols + col, row * new_cols + col);
+                }
+            }
+            self.cells.truncate(new_cols * rows);
+            self.alignments.retain(|&(col, _)| col < cols);
+            self.headers.retain(|&(_, col)| col < cols);
         }
+        self.num_cols = cols;
+
+        assert_eq!(self.num_cols as usize * self.num_rows as usize, self.cells.len());
     }
-    #[inline]
-    fn replace(&mut self, row: u32, col: u32, value: Cell<T>) -> Cell<T> {
-        let index = self.cell_index(row, col);
-        if let Some(cell) = self.cells.get_mut(index) {
-            replace(cell, value)
+    fn set_num_rows(&mut self, rows: u32) {
+        if rows > self.num_rows {
+            self.cells.extend(std::iter::from_fn(|| Some(Cell::Empty)).take((rows - self.num_rows) as usize * self.num_cols as usize));
         } else {
-            panic!("cell row={row}, col={col} out of bounds");
+            self.cells.truncate(rows as usize * self.num_cols as usize);
+            self.headers.retain(|&(row, _)| row < rows);
         }
+        self.num_rows = rows;
+
+        assert_eq!(self.num_cols as usize * self.num_rows as usize, self.cells.len());
     }
-    pub fn get_cell_value_mut(&mut self, row: u32, col: u32) -> Option<&mut T> {
-        let idx = self.cell_index(row, col);
-        match self.cells.get_mut(idx) {
-            Some(&mut Cell::Occupied { ref mut value, .. }) => Some(value),
-            Some(&mut Cell::Shadowed { col, row }) => {
-                debug!("shadowed cell at row={row}, col={col}");
-                None
+    /// Repairs the shadows after manual edits through `IndexMut` or untrusted input, so that `validate` passes.
+    /// All shadows are cleared and stamped again from the spans of the anchors, which are first clamped
+    /// to the table. Where spans overlap the later anchor in row-major order wins and the earlier one
+    /// shrinks to 1x1, so no value is lost.
+    pub fn compact(&mut self) {
+        for cell in self.cells.iter_mut() {
+            if cell.is_shadowed() {
+                *cell = Cell::Empty;
             }
-            Some(&mut Cell::Empty) => {
-                debug!("no data at row={row}, col={col}");
-                None
-            }
-            None => {
-                debug!("out of bounds row={row}, col={col}");
-                None
+        }
+        let (rows, cols) = (self.num_rows, self.num_cols);
+        // walking backwards, everything inside a span other than empty cells belongs to a later anchor
+        for i in (0 .. self.cells.len()).rev() {
+            let (row, col) = ((i / cols as usize) as u32, (i % cols as usize) as u32);
+            let (rowspan, colspan) = match self.cells[i] {
+                Cell::Occupied { rowspan, colspan, .. } => (rowspan.clamp(1, rows - row), colspan.clamp(1, cols - col)),
+                _ => continue
+            };
+            let free = (row .. row + rowspan).all(|r| (col .. col + colspan).all(|c| (r, c) == (row, col) || self[(r, c)].is_empty()));
+            let (rowspan, colspan) = if free { (rowspan, colspan) } else { (1, 1) };
+            if let Cell::Occupied { rowspan: ref mut old_rowspan, colspan: ref mut old_colspan, .. } = self.cells[i] {
+                *old_rowspan = rowspan;
+                *old_colspan = colspan;
             }
+            self.fill_span(row, col, rowspan, colspan);
         }
     }
-    pub fn format_html<W: fmt::Write>(&self, w: &mut W, format_cell: impl Fn(&mut W, &T) -> fmt::Result) -> fmt::Result {
-        assert_eq!(self.num_cols as usize * self.num_rows as usize, self.cells.len());
-        if self.num_cols == 0 || self.num_rows == 0 {
-            return Ok(());
+    /// Compares the dimensions and the anchors with their spans and values, ignoring how the shadows are stored.
+    /// Column alignments and header flags are not compared either.
+    pub fn visually_eq(&self, other: &Table<T>) -> bool where T: PartialEq {
+        (self.num_rows, self.num_cols) == (other.num_rows, other.num_cols)
+            && self.values().map(|cell| (cell.row, cell.col, cell.rowspan, cell.colspan, cell.value))
+                .eq(other.values().map(|cell| (cell.row, cell.col, cell.rowspan, cell.colspan, cell.value)))
+    }
+    /// Checks that every shadow points to an anchor covering it
+    /// and that every span is fully covered by shadows pointing back to its anchor.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if area(self.num_rows, self.num_cols) != Some(self.cells.len()) {
+            return Err(ValidationError::CellCount { rows: self.num_rows, cols: self.num_cols, len: self.cells.len() });
         }
-        writeln!(w, "<table>")?;
-        writeln!(w, "<tbody>")?;
-        for row in self.cells.chunks_exact(self.num_cols as usize) {
-            writeln!(w, "<tr>")?;
-            for cell in row {
-                match *cell {
-                    Cell::Empty => write!(w, "<td></td>")?,
-                    Cell::Occupied { ref value, colspan, rowspan } => {
-                        write!(w, "<td")?;
-                        if colspan != 1 {
-                            write!(w, " colspan={}", colspan)?;
+        for (row, col, cell) in self.cells_iter() {
+            match *cell {
+                Cell::Shadowed { col: anchor_col, row: anchor_row } => match self.get_cell(anchor_row, anchor_col) {
+                    None => return Err(ValidationError::ShadowOutOfBounds { row, col, anchor_row, anchor_col }),
+                    Some(&Cell::Occupied { rowspan, colspan, .. }) => {
+                        if row < anchor_row || row - anchor_row >= rowspan || col < anchor_col || col - anchor_col >= colspan {
+                            return Err(ValidationError::ShadowNotCovered { row, col, anchor_row, anchor_col });
                         }
-                        if rowspan != 1 {
-                            write!(w, " rowspan={}", rowspan)?;
+                    }
+                    Some(_) => return Err(ValidationError::ShadowWithoutAnchor { row, col, anchor_row, anchor_col }),
+                }
+                Cell::Occupied { rowspan, colspan, .. } => {
+                    if rowspan == 0 || colspan == 0 {
+                        return Err(ValidationError::EmptySpan { row, col });
+                    }
+                    if row.checked_add(rowspan).is_none_or(|end| end > self.num_rows) || col.checked_add(colspan).is_none_or(|end| end > self.num_cols) {
+                        return Err(ValidationError::SpanOutOfBounds { row, col });
+                    }
+                    for r in row .. row + rowspan {
+                        for c in col .. col + colspan {
+                            match self[(r, c)] {
+                                _ if (r, c) == (row, col) => {}
+                                Cell::Shadowed { col: anchor_col, row: anchor_row } if (anchor_row, anchor_col) == (row, col) => {}
+                                _ => return Err(ValidationError::MissingShadow { row: r, col: c, anchor_row: row, anchor_col: col }),
+                            }
                         }
-                        write!(w, ">")?;
-                        format_cell(w, value)?;
-                        writeln!(w, "</td>")?;
                     }
-                    Cell::Shadowed { .. } => {}
                 }
+                Cell::Empty => {}
             }
-            writeln!(w, "</tr>")?
         }
-        writeln!(w, "</tbody>")?;
-        writeln!(w, "</table>")?;
         Ok(())
     }
-    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> Table<U> {
-        Table {
-            num_cols: self.num_cols,
-            num_rows: self.num_rows,
-            cells: self.cells.into_iter().map(|cell| match cell {
-                Cell::Empty => Cell::Empty,
-                Cell::Occupied { value, colspan, rowspan } => Cell::Occupied { value: f(value), colspan, rowspan },
-                Cell::Shadowed { col, row } => Cell::Shadowed { col, row }
-            }).collect()
+    /// Merges the region into one spanning cell keeping the value at `row`, `col`.
+    /// The values of the other covered cells are dropped. Fails if the region touches another spanning cell.
+    pub fn merge(&mut self, row: u32, col: u32, rowspan: u32, colspan: u32) -> Result<(), Error> {
+        if rowspan == 0 || colspan == 0 {
+            return Err(Error::InvalidSpan { col, row });
         }
-    }
-    pub fn flat_map<U>(&self, mut f: impl FnMut(&T) -> Option<U>) -> Table<U> {
-        let mut deleted = HashSet::new();
-        Table {
-            num_cols: self.num_cols,
-            num_rows: self.num_rows,
-            cells: self.cells_iter().map(|(row, col, cell)| match *cell {
-                Cell::Empty => Cell::Empty,
-                Cell::Occupied { ref value, colspan, rowspan } => match f(value) {
-                    Some(value) => Cell::Occupied { value, colspan, rowspan },
-                    None => {
-                        if colspan != 1 && rowspan != 1 {
-                            deleted.insert((col, row));
-                        }
-                        Cell::Empty
-                    }
-                }
-                Cell::Shadowed { col, row } if deleted.contains(&(col, row)) => Cell::Empty,
-                Cell::Shadowed { col, row } => Cell::Shadowed { col, row },
-            }).collect()
+        if !self.span_in_bounds(row, col, rowspan, colspan) {
+            return Err(Error::OutOfBounds { row, col });
         }
+        match self[(row, col)] {
+            Cell::Occupied { .. } => {}
+            Cell::Shadowed { col, row } => return Err(Error::Shadowed { col, row }),
+            Cell::Empty => return Err(Error::Empty { col, row }),
+        }
+        for r in row .. row + rowspan {
+            for c in col .. col + colspan {
+                match self[(r, c)] {
+                    _ if (r, c) == (row, col) => {}
+                    Cell::Empty | Cell::Occupied { rowspan: 1, colspan: 1, .. } => {}
+                    Cell::Shadowed { col: anchor_col, row: anchor_row } if (anchor_row, anchor_col) == (row, col) => {}
+                    _ => return Err(Error::Overlap { col: c, row: r }),
+                }
+            }
+        }
+        let header = self.is_header(row, col);
+        let value = self.remove_cell(row, col).unwrap();
+        for r in row .. row + rowspan {
+            for c in col .. col + colspan {
+                self.set(r, c, Cell::Empty);
+            }
+        }
+        self.set_cell(value, row, col, rowspan, colspan)?;
+        self.remap_headers(|row, col| Some((row, col)));
+        if header {
+            self.set_header(row, col, true)?;
+        }
+        Ok(())
     }
-    pub fn values(&self) -> impl Iterator<Item=CellValue<T>> {
-        self.cells_iter().flat_map(|(row, col, cell)| match *cell {
-            Cell::Occupied { ref value, colspan, rowspan } => Some(CellValue {
-                value,
-                row, col,
-                rowspan, colspan
-            }),
+    /// Splits the spanning cell covering `row`, `col` back into a 1x1 anchor and empty cells.
+    pub fn unmerge(&mut self, row: u32, col: u32) -> Result<(), Error> {
+        let (row, col) = match self.get_cell(row, col) {
+            Some(Cell::Empty) => return Err(Error::Empty { col, row }),
+            None => return Err(Error::OutOfBounds { row, col }),
+            Some(_) => self.anchor_of(row, col).unwrap(),
+        };
+        let header = self.is_header(row, col);
+        let value = self.remove_cell(row, col).ok_or(Error::Empty { col, row })?;
+        self.set(row, col, Cell::Occupied { value, colspan: 1, rowspan: 1 });
+        if header {
+            self.set_header(row, col, true)?;
+        }
+        Ok(())
+    }
+    /// Swaps the values of the occupied cells at `a` and `b` given as `(row, col)`.
+    /// The spans stay where they are.
+    pub fn swap_cells(&mut self, a: (u32, u32), b: (u32, u32)) -> Result<(), Error> {
+        for (row, col) in [a, b] {
+            match self.get_cell(row, col) {
+                Some(Cell::Occupied { .. }) => {}
+                Some(&Cell::Shadowed { col, row }) => return Err(Error::Shadowed { col, row }),
+                Some(Cell::Empty) => return Err(Error::Empty { col, row }),
+                None => return Err(Error::OutOfBounds { row, col }),
+            }
+        }
+        if a == b {
+            return Ok(());
+        }
+        let (i, j) = (self.cell_index(a.0, a.1), self.cell_index(b.0, b.1));
+        let (head, tail) = self.cells.split_at_mut(i.max(j));
+        if let (Cell::Occupied { value: x, .. }, Cell::Occupied { value: y, .. }) = (&mut head[i.min(j)], &mut tail[0]) {
+            std::mem::swap(x, y);
+        }
+        Ok(())
+    }
+    /// Swaps rows `a` and `b`. Fails without changing anything if either row is out of bounds or part of a vertical span.
+    pub fn swap_rows(&mut self, a: u32, b: u32) -> Result<(), Error> {
+        if let Some(row) = [a, b].into_iter().find(|&row| row >= self.num_rows) {
+            return Err(Error::OutOfBounds { row, col: 0 });
+        }
+        if a == b {
+            return Ok(());
+        }
+        for row in [a, b] {
+            for (col, cell) in self.row(row).unwrap().iter().enumerate() {
+                match *cell {
+                    Cell::Occupied { rowspan, .. } if rowspan > 1 => return Err(Error::SplitSpan { col: col as u32, row }),
+                    Cell::Shadowed { col, row: anchor_row } if anchor_row != row => return Err(Error::SplitSpan { col, row: anchor_row }),
+                    _ => {}
+                }
+            }
+        }
+        let (low, high) = (a.min(b), a.max(b));
+        let (start, mid) = (self.cell_index(low, 0), self.cell_index(high, 0));
+        let (head, tail) = self.cells.split_at_mut(mid);
+        head[start .. start + self.num_cols as usize].swap_with_slice(&mut tail[.. self.num_cols as usize]);
+        for (new_row, old_row) in [(a, b), (b, a)] {
+            for cell in self.row_mut(new_row).unwrap() {
+                if let Cell::Shadowed { ref mut row, .. } = *cell {
+                    debug_assert_eq!(*row, old_row);
+                    *row = new_row;
+                }
+            }
+        }
+        self.remap_headers(|row, col| Some((if row == a { b } else if row == b { a } else { row }, col)));
+        Ok(())
+    }
+    /// Swaps columns `a` and `b`. Fails without changing anything if either column is out of bounds or part of a horizontal span.
+    pub fn swap_columns(&mut self, a: u32, b: u32) -> Result<(), Error> {
+        if let Some(col) = [a, b].into_iter().find(|&col| col >= self.num_cols) {
+            return Err(Error::OutOfBounds { row: 0, col });
+        }
+        if a == b {
+            return Ok(());
+        }
+        for col in [a, b] {
+            for row in 0 .. self.num_rows {
+                match self[(row, col)] {
+                    Cell::Occupied { colspan, .. } if colspan > 1 => return Err(Error::SplitSpan { col, row }),
+                    Cell::Shadowed { col: anchor_col, row } if anchor_col != col => return Err(Error::SplitSpan { col: anchor_col, row }),
+                    _ => {}
+                }
+            }
+        }
+        for row in 0 .. self.num_rows {
+            let (i, j) = (self.cell_index(row, a), self.cell_index(row, b));
+            self.cells.swap(i, j);
+            for (index, new_col) in [(i, a), (j, b)] {
+                if let Cell::Shadowed { ref mut col, .. } = self.cells[index] {
+                    *col = new_col;
+                }
+            }
+        }
+        self.remap_alignments(|col| Some(if col == a { b } else if col == b { a } else { col }));
+        self.remap_headers(|row, col| Some((row, if col == a { b } else if col == b { a } else { col })));
+        Ok(())
+    }
+    /// Whether the span fits inside the table, without overflowing on huge spans.
+    pub(crate) fn span_in_bounds(&self, row: u32, col: u32, rowspan: u32, colspan: u32) -> bool {
+        row.checked_add(rowspan).is_some_and(|end| end <= self.num_rows) && col.checked_add(colspan).is_some_and(|end| end <= self.num_cols)
+    }
+    /// Panics if the index does not fit in `usize`, which can only happen on 32-bit targets.
+    #[inline]
+    fn cell_index(&self, row: u32, col: u32) -> usize {
+        (self.num_cols as usize).checked_mul(row as usize).and_then(|i| i.checked_add(col as usize))
+            .unwrap_or_else(|| panic!("index of cell row={row}, col={col} overflows usize"))
+    }
+    #[inline]
+    fn set(&mut self, row: u32, col: u32, value: Cell<T>) {
+        let index = self.cell_index(row, col);
+        if let Some(cell) = self.cells.get_mut(index) {
+            *cell = value;
+        } else {
+            panic!("cell row={row}, col={col} out of bounds");
+        }
+    }
+    #[inline]
+    fn replace(&mut self, row: u32, col: u32, value: Cell<T>) -> Cell<T> {
+        let index = self.cell_index(row, col);
+        if let Some(cell) = self.cells.get_mut(index) {
+            replace(cell, value)
+        } else {
+            panic!("cell row={row}, col={col} out of bounds");
+        }
+    }
+    pub fn get_cell(&self, row: u32, col: u32) -> Option<&Cell<T>> {
+        if row >= self.num_rows || col >= self.num_cols {
+            return None;
+        }
+        self.cells.get(self.cell_index(row, col))
+    }
+    pub fn get_cell_mut(&mut self, row: u32, col: u32) -> Option<&mut Cell<T>> {
+        if row >= self.num_rows || col >= self.num_cols {
+            return None;
+        }
+        let idx = self.cell_index(row, col);
+        self.cells.get_mut(idx)
+    }
+    pub fn row(&self, row: u32) -> Option<&[Cell<T>]> {
+        if row >= self.num_rows {
+            return None;
+        }
+        let start = self.cell_index(row, 0);
+        Some(&self.cells[start .. start + self.num_cols as usize])
+    }
+    /// Yields one iterator over the cells of each row, top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item=impl Iterator<Item=&Cell<T>>> {
+        (0 .. self.num_rows).map(move |row| self.row(row).unwrap().iter())
+    }
+    /// The occupied cells anchored in `row`. Spans reaching down from earlier rows are not included,
+    /// so each value shows up in exactly one row. `None` if `row` is out of bounds.
+    pub fn row_values(&self, row: u32) -> Option<impl Iterator<Item=CellValue<'_, T>>> {
+        let cells = self.row(row)?;
+        Some(cells.iter().enumerate().filter_map(move |(col, cell)| match *cell {
+            Cell::Occupied { ref value, colspan, rowspan } => Some(CellValue { value, row, col: col as u32, rowspan, colspan }),
+            _ => None
+        }))
+    }
+    pub fn row_mut(&mut self, row: u32) -> Option<&mut [Cell<T>]> {
+        if row >= self.num_rows {
+            return None;
+        }
+        let start = self.cell_index(row, 0);
+        Some(&mut self.cells[start .. start + self.num_cols as usize])
+    }
+    pub fn column(&self, col: u32) -> Option<impl Iterator<Item=&Cell<T>>> {
+        if col >= self.num_cols {
+            return None;
+        }
+        // a table without rows has no cells to slice into
+        Some(self.cells.get(col as usize ..).unwrap_or(&[]).iter().step_by(self.num_cols as usize))
+    }
+    pub fn anchor_of(&self, row: u32, col: u32) -> Option<(u32, u32)> {
+        match *self.get_cell(row, col)? {
+            Cell::Occupied { .. } => Some((row, col)),
+            Cell::Shadowed { col, row } => Some((row, col)),
+            Cell::Empty => None,
+        }
+    }
+    /// Returns `(rowspan, colspan)` of the occupied cell covering `row`, `col`.
+    pub fn span_at(&self, row: u32, col: u32) -> Option<(u32, u32)> {
+        let (row, col) = self.anchor_of(row, col)?;
+        match *self.get_cell(row, col)? {
+            Cell::Occupied { rowspan, colspan, .. } => Some((rowspan, colspan)),
+            _ => None
+        }
+    }
+    /// The anchor covering `row`, `col`, whether that is the anchor itself or one of its shadows.
+    /// This is the lookup for hit-testing merged cells.
+    pub fn cell_at(&self, row: u32, col: u32) -> Option<CellValue<'_, T>> {
+        let (row, col) = self.anchor_of(row, col)?;
+        match *self.get_cell(row, col)? {
+            Cell::Occupied { ref value, colspan, rowspan } => Some(CellValue { value, row, col, rowspan, colspan }),
+            _ => None
+        }
+    }
+    /// Yields the in-bounds cells above, below, left and right of `row`, `col`.
+    pub fn neighbors(&self, row: u32, col: u32) -> impl Iterator<Item=(u32, u32, &Cell<T>)> {
+        let candidates = [
+            row.checked_sub(1).map(|r| (r, col)),
+            row.checked_add(1).map(|r| (r, col)),
+            col.checked_sub(1).map(|c| (row, c)),
+            col.checked_add(1).map(|c| (row, c)),
+        ];
+        candidates.into_iter().flatten().filter_map(move |(r, c)| self.get_cell(r, c).map(|cell| (r, c, cell)))
+    }
+    pub fn get_cell_value(&self, row: u32, col: u32) -> Option<&T> {
+        match self.get_cell(row, col) {
+            Some(Cell::Occupied { value, .. }) => Some(value),
+            Some(&Cell::Shadowed { col, row }) => {
+                debug!("shadowed cell at row={row}, col={col}");
+                None
+            }
+            Some(&Cell::Empty) => {
+                debug!("no data at row={row}, col={col}");
+                None
+            }
+            None => {
+                debug!("out of bounds row={row}, col={col}");
+                None
+            }
+        }
+    }
+    /// Returns the value at `row`, `col`, placing a 1x1 cell from `f()` first if there is none,
+    /// growing the table like `set_cell`. A shadowed coordinate yields the value of its anchor.
+    /// Panics with the `set_cell` error if a new cell is needed at row or column `u32::MAX`.
+    pub fn get_or_insert_with(&mut self, row: u32, col: u32, f: impl FnOnce() -> T) -> &mut T {
+        let (row, col) = match self.anchor_of(row, col) {
+            Some(anchor) => anchor,
+            None => {
+                self.set_cell(f(), row, col, 1, 1).unwrap_or_else(|err| panic!("{err}"));
+                (row, col)
+            }
+        };
+        match self[(row, col)] {
+            Cell::Occupied { ref mut value, .. } => value,
+            _ => panic!("shadow at row={row}, col={col} has no anchor")
+        }
+    }
+    pub fn get_cell_value_mut(&mut self, row: u32, col: u32) -> Option<&mut T> {
+        match self.get_cell_mut(row, col) {
+            Some(&mut Cell::Occupied { ref mut value, .. }) => Some(value),
+            Some(&mut Cell::Shadowed { col, row }) => {
+                debug!("shadowed cell at row={row}, col={col}");
+                None
+            }
+            Some(&mut Cell::Empty) => {
+                debug!("no data at row={row}, col={col}");
+                None
+            }
+            None => {
+                debug!("out of bounds row={row}, col={col}");
+                None
+            }
+        }
+    }
+    /// Like `get_cell_value_mut`, but tells apart why there is no value.
+    /// On a shadow the anchor is returned so the caller can follow it.
+    pub fn get_value_mut(&mut self, row: u32, col: u32) -> Result<&mut T, CellError> {
+        match self.get_cell_mut(row, col) {
+            Some(&mut Cell::Occupied { ref mut value, .. }) => Ok(value),
+            Some(&mut Cell::Shadowed { col, row }) => Err(CellError::Shadowed { anchor: (row, col) }),
+            Some(&mut Cell::Empty) => Err(CellError::Empty),
+            None => Err(CellError::OutOfBounds),
+        }
+    }
+    /// Runs `f` on the value covering `row`, `col`, following a shadow to its anchor.
+    pub fn update(&mut self, row: u32, col: u32, f: impl FnOnce(&mut T)) -> Result<(), CellError> {
+        let (row, col) = match self.get_value_mut(row, col) {
+            Ok(value) => {
+                f(value);
+                return Ok(());
+            }
+            Err(CellError::Shadowed { anchor }) => anchor,
+            Err(e) => return Err(e),
+        };
+        f(self.get_value_mut(row, col)?);
+        Ok(())
+    }
+    /// `format_html` into an `io::Write`, e.g. a file or socket, without rendering into a `String` first.
+    pub fn write_html<W: io::Write>(&self, w: W, format_cell: impl Fn(&mut IoWriter<W>, &T) -> fmt::Result) -> io::Result<()> {
+        let mut w = IoWriter::new(w);
+        let result = self.format_html(&mut w, format_cell);
+        w.finish(result).map(drop)
+    }
+    /// `format_csv` into an `io::Write`.
+    pub fn write_csv<W: io::Write>(&self, w: W, format_cell: impl Fn(&mut String, &T) -> fmt::Result) -> io::Result<()> {
+        let mut w = IoWriter::new(w);
+        let result = self.format_csv(&mut w, format_cell);
+        w.finish(result).map(drop)
+    }
+    pub fn format_html<W: fmt::Write>(&self, w: &mut W, format_cell: impl Fn(&mut W, &T) -> fmt::Result) -> fmt::Result {
+        self.format_html_with_header(w, 0, format_cell)
+    }
+    /// Like `format_html`, but the first `header_rows` rows go into a `<thead>` using `<th>` cells.
+    pub fn format_html_with_header<W: fmt::Write>(&self, w: &mut W, header_rows: u32, format_cell: impl Fn(&mut W, &T) -> fmt::Result) -> fmt::Result {
+        self.format_html_impl(w, header_rows, HtmlStyle { tags: &HtmlTags::default(), empty: &EmptyCellStyle::Empty }, format_cell, |_, _, _| String::new())
+    }
+    /// Like `format_html`, but `attrs(row, col, value)` supplies extra attributes for the `<td>` of each occupied cell.
+    /// The returned string is inserted as-is, e.g. `class="num"`.
+    pub fn format_html_attrs<W: fmt::Write>(&self, w: &mut W, format_cell: impl Fn(&mut W, &T) -> fmt::Result, attrs: impl Fn(u32, u32, &T) -> String) -> fmt::Result {
+        self.format_html_impl(w, 0, HtmlStyle { tags: &HtmlTags::default(), empty: &EmptyCellStyle::Empty }, format_cell, attrs)
+    }
+    /// Like `format_html`, but `empty` controls how empty cells are rendered.
+    pub fn format_html_styled<W: fmt::Write>(&self, w: &mut W, empty: &EmptyCellStyle, format_cell: impl Fn(&mut W, &T) -> fmt::Result) -> fmt::Result {
+        self.format_html_impl(w, 0, HtmlStyle { tags: &HtmlTags::default(), empty }, format_cell, |_, _, _| String::new())
+    }
+    /// Like `format_html_with_header`, but with the element names taken from `tags`.
+    pub fn format_html_with_tags<W: fmt::Write>(&self, w: &mut W, tags: &HtmlTags, header_rows: u32, format_cell: impl Fn(&mut W, &T) -> fmt::Result) -> fmt::Result {
+        self.format_html_impl(w, header_rows, HtmlStyle { tags, empty: &EmptyCellStyle::Empty }, format_cell, |_, _, _| String::new())
+    }
+    fn format_html_impl<W: fmt::Write>(&self, w: &mut W, header_rows: u32, style: HtmlStyle, format_cell: impl Fn(&mut W, &T) -> fmt::Result, attrs: impl Fn(u32, u32, &T) -> String) -> fmt::Result {
+        assert_eq!(self.num_cols as usize * self.num_rows as usize, self.cells.len());
+        if self.num_cols == 0 || self.num_rows == 0 {
+            return Ok(());
+        }
+        let tags = style.tags;
+        if let Some(ref table) = tags.table {
+            writeln!(w, "<{table}>")?;
+        }
+        let mut rows = 0 .. self.num_rows;
+        if header_rows > 0 {
+            if let Some(ref thead) = tags.thead {
+                writeln!(w, "<{thead}>")?;
+            }
+            for row_nr in rows.by_ref().take(header_rows as usize) {
+                self.format_html_row(w, row_nr, true, style, &format_cell, &attrs)?;
+            }
+            if let Some(ref thead) = tags.thead {
+                writeln!(w, "</{thead}>")?;
+            }
+        }
+        if let Some(ref tbody) = tags.tbody {
+            writeln!(w, "<{tbody}>")?;
+        }
+        for row_nr in rows {
+            self.format_html_row(w, row_nr, false, style, &format_cell, &attrs)?;
+        }
+        if let Some(ref tbody) = tags.tbody {
+            writeln!(w, "</{tbody}>")?;
+        }
+        if let Some(ref table) = tags.table {
+            writeln!(w, "</{table}>")?;
+        }
+        Ok(())
+    }
+    fn format_html_row<W: fmt::Write>(&self, w: &mut W, row_nr: u32, header: bool, style: HtmlStyle, format_cell: &impl Fn(&mut W, &T) -> fmt::Result, attrs: &impl Fn(u32, u32, &T) -> String) -> fmt::Result {
+        let tags = style.tags;
+        let tag = if header { &tags.th } else { &tags.td };
+        writeln!(w, "<{}>", tags.tr)?;
+        for (col_nr, cell) in self.row(row_nr).unwrap().iter().enumerate() {
+            match *cell {
+                Cell::Empty => match *style.empty {
+                    EmptyCellStyle::Empty => write!(w, "<{tag}></{tag}>")?,
+                    EmptyCellStyle::Omit => {}
+                    EmptyCellStyle::Placeholder(ref html) => write!(w, "<{tag}>{html}</{tag}>")?,
+                },
+                Cell::Occupied { ref value, colspan, rowspan } => {
+                    let tag = if self.is_header(row_nr, col_nr as u32) { &tags.th } else { tag };
+                    write!(w, "<{tag}")?;
+                    let mut attrs = attrs(row_nr, col_nr as u32, value);
+                    let align = match self.column_alignment(col_nr as u32) {
+                        Alignment::Left => None,
+                        Alignment::Center => Some("text-align: center"),
+                        Alignment::Right => Some("text-align: right"),
+                    };
+                    // fold the alignment into a `style` from `attrs` instead of emitting a second one
+                    match (align, style_value_start(&attrs)) {
+                        (Some(align), Some(start)) => attrs.insert_str(start, &format!("{align}; ")),
+                        (Some(align), None) if attrs.is_empty() => attrs = format!("style=\"{align}\""),
+                        (Some(align), None) => attrs.push_str(&format!(" style=\"{align}\"")),
+                        (None, _) => {}
+                    }
+                    if !attrs.is_empty() {
+                        write!(w, " {}", attrs)?;
+                    }
+                    if colspan != 1 {
+                        write!(w, " colspan={}", colspan)?;
+                    }
+                    if rowspan != 1 {
+                        write!(w, " rowspan={}", rowspan)?;
+                    }
+                    write!(w, ">")?;
+                    format_cell(w, value)?;
+                    writeln!(w, "</{tag}>")?;
+                }
+                Cell::Shadowed { .. } => {}
+            }
+        }
+        writeln!(w, "</{}>", tags.tr)
+    }
+    /// Cells are rendered into a scratch `String` first so fields containing
+    /// commas, quotes or newlines can be quoted per RFC 4180.
+    /// Spanning cells are written at their anchor, shadowed coordinates become empty fields.
+    pub fn format_csv<W: fmt::Write>(&self, w: &mut W, format_cell: impl Fn(&mut String, &T) -> fmt::Result) -> fmt::Result {
+        assert_eq!(self.num_cols as usize * self.num_rows as usize, self.cells.len());
+        if self.num_cols == 0 || self.num_rows == 0 {
+            return Ok(());
+        }
+        let mut buf = String::new();
+        for row in self.cells.chunks_exact(self.num_cols as usize) {
+            for (col, cell) in row.iter().enumerate() {
+                if col != 0 {
+                    write!(w, ",")?;
+                }
+                if let Cell::Occupied { ref value, .. } = *cell {
+                    buf.clear();
+                    format_cell(&mut buf, value)?;
+                    if buf.contains([',', '"', '\n', '\r']) {
+                        write!(w, "\"{}\"", buf.replace('"', "\"\""))?;
+                    } else {
+                        write!(w, "{}", buf)?;
+                    }
+                }
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+    /// Tab separated values, with spanning cells written at their anchor and shadowed coordinates left empty.
+    /// TSV has no quoting, so tabs or newlines written by `format_cell` corrupt the output; escaping is up to the caller.
+    pub fn format_tsv<W: fmt::Write>(&self, w: &mut W, format_cell: impl Fn(&mut W, &T) -> fmt::Result) -> fmt::Result {
+        assert_eq!(self.num_cols as usize * self.num_rows as usize, self.cells.len());
+        if self.num_cols == 0 || self.num_rows == 0 {
+            return Ok(());
+        }
+        for row in self.cells.chunks_exact(self.num_cols as usize) {
+            for (col, cell) in row.iter().enumerate() {
+                if col != 0 {
+                    write!(w, "\t")?;
+                }
+                if let Cell::Occupied { ref value, .. } = *cell {
+                    format_cell(w, value)?;
+                }
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+    /// The first row becomes the header. Markdown has no colspan/rowspan,
+    /// so spans are lossy: the value stays at the anchor and shadowed cells are left blank.
+    pub fn format_markdown<W: fmt::Write>(&self, w: &mut W, format_cell: impl Fn(&mut String, &T) -> fmt::Result) -> fmt::Result {
+        assert_eq!(self.num_cols as usize * self.num_rows as usize, self.cells.len());
+        if self.num_cols == 0 || self.num_rows == 0 {
+            return Ok(());
+        }
+        let mut buf = String::new();
+        for (row_nr, row) in self.cells.chunks_exact(self.num_cols as usize).enumerate() {
+            write!(w, "|")?;
+            for cell in row {
+                if let Cell::Occupied { ref value, .. } = *cell {
+                    buf.clear();
+                    format_cell(&mut buf, value)?;
+                    write!(w, " {} ", buf.replace('|', "\\|"))?;
+                } else {
+                    write!(w, " ")?;
+                }
+                write!(w, "|")?;
+            }
+            writeln!(w)?;
+            if row_nr == 0 {
+                write!(w, "|")?;
+                for col in 0 .. self.num_cols {
+                    match self.column_alignment(col) {
+                        Alignment::Left => write!(w, "---|")?,
+                        Alignment::Center => write!(w, ":---:|")?,
+                        Alignment::Right => write!(w, "---:|")?,
+                    }
+                }
+                writeln!(w)?;
+            }
+        }
+        Ok(())
+    }
+    /// Emits a `tabular` environment. Vertical spans use `\multirow`,
+    /// so the document needs `\usepackage{multirow}`.
+    pub fn format_latex<W: fmt::Write>(&self, w: &mut W, format_cell: impl Fn(&mut W, &T) -> fmt::Result) -> fmt::Result {
+        assert_eq!(self.num_cols as usize * self.num_rows as usize, self.cells.len());
+        if self.num_cols == 0 || self.num_rows == 0 {
+            return Ok(());
+        }
+        writeln!(w, "\\begin{{tabular}}{{{}}}", "c".repeat(self.num_cols as usize))?;
+        for (row_nr, row) in self.cells.chunks_exact(self.num_cols as usize).enumerate() {
+            let mut first = true;
+            for (col_nr, cell) in row.iter().enumerate() {
+                let colspan = match *cell {
+                    Cell::Shadowed { col, row } if row as usize != row_nr && col as usize == col_nr => match self[(row, col)] {
+                        Cell::Occupied { colspan, .. } => colspan,
+                        _ => 1
+                    },
+                    Cell::Shadowed { .. } => continue,
+                    Cell::Occupied { colspan, .. } => colspan,
+                    Cell::Empty => 1,
+                };
+                if !first {
+                    write!(w, " & ")?;
+                }
+                first = false;
+                if colspan != 1 {
+                    write!(w, "\\multicolumn{{{}}}{{c}}{{", colspan)?;
+                }
+                if let Cell::Occupied { ref value, rowspan, .. } = *cell {
+                    if rowspan != 1 {
+                        write!(w, "\\multirow{{{}}}{{*}}{{", rowspan)?;
+                        format_cell(w, value)?;
+                        write!(w, "}}")?;
+                    } else {
+                        format_cell(w, value)?;
+                    }
+                }
+                if colspan != 1 {
+                    write!(w, "}}")?;
+                }
+            }
+            writeln!(w, " \\\\")?;
+        }
+        writeln!(w, "\\end{{tabular}}")?;
+        Ok(())
+    }
+    /// Draws the table with Unicode box-drawing characters.
+    /// Cells are rendered into a scratch `String` to measure the column widths and are expected to be a single line.
+    /// Spanning cells are drawn as one larger box.
+    pub fn format_box<W: fmt::Write>(&self, w: &mut W, format_cell: impl Fn(&mut String, &T) -> fmt::Result) -> fmt::Result {
+        assert_eq!(self.num_cols as usize * self.num_rows as usize, self.cells.len());
+        if self.num_cols == 0 || self.num_rows == 0 {
+            return Ok(());
+        }
+        let (rows, cols) = (self.num_rows as usize, self.num_cols as usize);
+        let mut texts = Vec::with_capacity(self.cells.len());
+        for cell in self.cells.iter() {
+            let mut text = String::new();
+            if let Cell::Occupied { ref value, .. } = *cell {
+                format_cell(&mut text, value)?;
+            }
+            texts.push(text);
+        }
+
+        let mut widths = vec![0; cols];
+        for (row, col, cell) in self.cells_iter() {
+            if let Cell::Occupied { colspan: 1, .. } = *cell {
+                let len = texts[self.cell_index(row, col)].chars().count();
+                widths[col as usize] = widths[col as usize].max(len);
+            }
+        }
+        for (row, col, cell) in self.cells_iter() {
+            if let Cell::Occupied { colspan, .. } = *cell {
+                let span = col as usize .. (col + colspan) as usize;
+                let len = texts[self.cell_index(row, col)].chars().count();
+                let available = widths[span.clone()].iter().sum::<usize>() + 3 * (colspan as usize - 1);
+                if len > available {
+                    widths[span.end - 1] += len - available;
+                }
+            }
+        }
+
+        let anchor = |row: usize, col: usize| self.anchor_of(row as u32, col as u32);
+        let split = |a: Option<(u32, u32)>, b: Option<(u32, u32)>| a.is_none() || a != b;
+        // line between row - 1 and row, above column col
+        let horizontal = |row: usize, col: usize| row == 0 || row == rows || split(anchor(row - 1, col), anchor(row, col));
+        // line between col - 1 and col, next to row row
+        let vertical = |row: usize, col: usize| col == 0 || col == cols || split(anchor(row, col - 1), anchor(row, col));
+        let width_of = |col: usize, colspan: usize| widths[col .. col + colspan].iter().sum::<usize>() + 3 * (colspan - 1);
+
+        for row in 0 ..= rows {
+            for col in 0 ..= cols {
+                let up = row > 0 && vertical(row - 1, col);
+                let down = row < rows && vertical(row, col);
+                let left = col > 0 && horizontal(row, col - 1);
+                let right = col < cols && horizontal(row, col);
+                let junction = match (up, down, left, right) {
+                    (true, true, true, true) => '┼',
+                    (true, true, false, true) => '├',
+                    (true, true, true, false) => '┤',
+                    (false, true, true, true) => '┬',
+                    (true, false, true, true) => '┴',
+                    (false, true, false, true) => '┌',
+                    (false, true, true, false) => '┐',
+                    (true, false, false, true) => '└',
+                    (true, false, true, false) => '┘',
+                    (true, _, false, false) | (false, true, false, false) => '│',
+                    (false, false, true, _) | (false, false, false, true) => '─',
+                    (false, false, false, false) => ' ',
+                };
+                write!(w, "{}", junction)?;
+                if col < cols {
+                    let fill = if horizontal(row, col) { "─" } else { " " };
+                    write!(w, "{}", fill.repeat(width_of(col, 1) + 2))?;
+                }
+            }
+            writeln!(w)?;
+            if row == rows {
+                break;
+            }
+
+            let mut col = 0;
+            while col < cols {
+                let (text, colspan) = match self.cells[self.cell_index(row as u32, col as u32)] {
+                    Cell::Occupied { colspan, .. } => (texts[self.cell_index(row as u32, col as u32)].as_str(), colspan as usize),
+                    Cell::Shadowed { col: anchor_col, row: anchor_row } => match self[(anchor_row, anchor_col)] {
+                        Cell::Occupied { colspan, .. } => ("", colspan as usize - (col - anchor_col as usize)),
+                        _ => ("", 1)
+                    },
+                    Cell::Empty => ("", 1)
+                };
+                match self.column_alignment(col as u32) {
+                    Alignment::Left => write!(w, "│ {:<width$} ", text, width=width_of(col, colspan))?,
+                    Alignment::Center => write!(w, "│ {:^width$} ", text, width=width_of(col, colspan))?,
+                    Alignment::Right => write!(w, "│ {:>width$} ", text, width=width_of(col, colspan))?,
+                }
+                col += colspan;
+            }
+            writeln!(w, "│")?;
+        }
+        Ok(())
+    }
+    /// Consumes the table, yielding `(row, col, value, rowspan, colspan)` for every occupied cell in reading order.
+    pub fn into_values(self) -> impl Iterator<Item=(u32, u32, T, u32, u32)> {
+        let num_cols = self.num_cols.max(1) as usize;
+        self.cells.into_iter().enumerate().filter_map(move |(i, cell)| match cell {
+            Cell::Occupied { value, colspan, rowspan } => Some(((i / num_cols) as u32, (i % num_cols) as u32, value, rowspan, colspan)),
             _ => None
         })
     }
-    fn cells_iter(&self) -> impl Iterator<Item=(u32, u32, &Cell<T>)> {
-        self.cells.chunks_exact(self.num_cols as usize).enumerate().
-        flat_map(|(row, cells)|
-            cells.iter().enumerate().map(move |(col, cell)| (row as u32, col as u32, cell))
-        )
+    /// The inverse of `from_rows`: occupied anchors become `Some`, empty and shadowed coordinates `None`.
+    pub fn into_rows(self) -> Vec<Vec<Option<T>>> {
+        let mut cells = self.cells.into_iter().map(|cell| match cell {
+            Cell::Occupied { value, .. } => Some(value),
+            _ => None
+        });
+        (0 .. self.num_rows).map(|_| cells.by_ref().take(self.num_cols as usize).collect()).collect()
+    }
+    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> Table<U> {
+        Table {
+            num_cols: self.num_cols,
+            num_rows: self.num_rows,
+            cells: self.cells.into_iter().map(|cell| match cell {
+                Cell::Empty => Cell::Empty,
+                Cell::Occupied { value, colspan, rowspan } => Cell::Occupied { value: f(value), colspan, rowspan },
+                Cell::Shadowed { col, row } => Cell::Shadowed { col, row }
+            }).collect(),
+            alignments: self.alignments,
+            headers: self.headers
+        }
+    }
+    /// Like `map`, but also passes the anchor coordinates: `f(row, col, value)`.
+    pub fn map_with_coords<U>(self, mut f: impl FnMut(u32, u32, T) -> U) -> Table<U> {
+        let num_cols = self.num_cols.max(1) as usize;
+        Table {
+            num_cols: self.num_cols,
+            num_rows: self.num_rows,
+            cells: self.cells.into_iter().enumerate().map(|(i, cell)| match cell {
+                Cell::Empty => Cell::Empty,
+                Cell::Occupied { value, colspan, rowspan } => Cell::Occupied { value: f((i / num_cols) as u32, (i % num_cols) as u32, value), colspan, rowspan },
+                Cell::Shadowed { col, row } => Cell::Shadowed { col, row }
+            }).collect(),
+            alignments: self.alignments,
+            headers: self.headers
+        }
+    }
+    /// Stops at the first error; the partially mapped table is discarded.
+    pub fn try_map<U, E>(self, mut f: impl FnMut(T) -> Result<U, E>) -> Result<Table<U>, E> {
+        Ok(Table {
+            num_cols: self.num_cols,
+            num_rows: self.num_rows,
+            cells: self.cells.into_iter().map(|cell| Ok(match cell {
+                Cell::Empty => Cell::Empty,
+                Cell::Occupied { value, colspan, rowspan } => Cell::Occupied { value: f(value)?, colspan, rowspan },
+                Cell::Shadowed { col, row } => Cell::Shadowed { col, row }
+            })).collect::<Result<_, E>>()?,
+            alignments: self.alignments,
+            headers: self.headers
+        })
+    }
+    /// Builds a transformed copy while leaving `self` untouched.
+    /// Prefer `map` when the original table is no longer needed, it moves the values instead of borrowing them.
+    pub fn clone_map<U>(&self, mut f: impl FnMut(&T) -> U) -> Table<U> {
+        Table {
+            num_cols: self.num_cols,
+            num_rows: self.num_rows,
+            cells: self.cells_iter().map(|(_, _, cell)| match *cell {
+                Cell::Empty => Cell::Empty,
+                Cell::Occupied { ref value, colspan, rowspan } => Cell::Occupied { value: f(value), colspan, rowspan },
+                Cell::Shadowed { col, row } => Cell::Shadowed { col, row }
+            }).collect(),
+            alignments: self.alignments.clone(),
+            headers: self.headers.clone()
+        }
+    }
+    /// Like `map` for `T` to `T`, but changes the values where they are instead of building new storage.
+    /// Spans and shadows are not touched.
+    pub fn map_in_place(&mut self, mut f: impl FnMut(&mut T)) {
+        for cell in self.cells.iter_mut() {
+            if let Cell::Occupied { ref mut value, .. } = *cell {
+                f(value);
+            }
+        }
+    }
+    pub fn map_ref<U>(&self, mut f: impl FnMut(&T) -> U) -> Table<U> {
+        Table {
+            num_cols: self.num_cols,
+            num_rows: self.num_rows,
+            cells: self.cells.iter().map(|cell| match *cell {
+                Cell::Empty => Cell::Empty,
+                Cell::Occupied { ref value, colspan, rowspan } => Cell::Occupied { value: f(value), colspan, rowspan },
+                Cell::Shadowed { col, row } => Cell::Shadowed { col, row }
+            }).collect(),
+            alignments: self.alignments.clone(),
+            headers: self.headers.clone()
+        }
+    }
+    /// Columns turn into rows, so the column alignments are dropped.
+    pub fn transpose(self) -> Table<T> {
+        let Table { num_cols, num_rows, mut cells, headers, .. } = self;
+        let mut transposed = Vec::with_capacity(cells.len());
+        for col in 0 .. num_cols as usize {
+            for row in 0 .. num_rows as usize {
+                transposed.push(match replace(&mut cells[row * num_cols as usize + col], Cell::Empty) {
+                    Cell::Empty => Cell::Empty,
+                    Cell::Occupied { value, colspan, rowspan } => Cell::Occupied { value, colspan: rowspan, rowspan: colspan },
+                    Cell::Shadowed { col, row } => Cell::Shadowed { col: row, row: col }
+                });
+            }
+        }
+        Table {
+            num_cols: num_rows,
+            num_rows: num_cols,
+            cells: transposed,
+            alignments: vec![],
+            headers: {
+                let mut headers: Vec<_> = headers.into_iter().map(|(row, col)| (col, row)).collect();
+                headers.sort_unstable();
+                headers
+            }
+        }
+    }
+    /// Rotates the table 90 degrees clockwise.
+    /// Columns turn into rows, so the column alignments are dropped.
+    pub fn rotate_cw(self) -> Table<T> {
+        let (rows, cols) = (self.num_rows, self.num_cols);
+        self.remap(cols, rows, |row, col, rowspan, colspan| (col, rows - row - rowspan, colspan, rowspan))
+    }
+    /// Rotates the table 90 degrees counter-clockwise.
+    /// Columns turn into rows, so the column alignments are dropped.
+    pub fn rotate_ccw(self) -> Table<T> {
+        let (rows, cols) = (self.num_rows, self.num_cols);
+        self.remap(cols, rows, |row, col, rowspan, colspan| (cols - col - colspan, row, colspan, rowspan))
+    }
+    /// Copies the `rows` x `cols` window starting at `row`, `col` into a new table.
+    /// Cells are only copied if their anchor lies inside the window; their spans are truncated
+    /// at the window edge. Shadows of anchors outside the window become empty.
+    pub fn subtable(&self, row: u32, col: u32, rows: u32, cols: u32) -> Table<T> where T: Clone {
+        let mut table = Table::empty(rows, cols);
+        for r in 0 .. rows {
+            for c in 0 .. cols {
+                if let Some(Cell::Occupied { value, rowspan, colspan }) = self.get_cell(row + r, col + c) {
+                    let (rowspan, colspan) = ((*rowspan).min(rows - r), (*colspan).min(cols - c));
+                    table.set(r, c, Cell::Occupied { value: value.clone(), rowspan, colspan });
+                    table.fill_span(r, c, rowspan, colspan);
+                }
+            }
+        }
+        table.alignments = self.alignments.iter().filter(|&&(c, _)| c >= col && c - col < cols).map(|&(c, align)| (c - col, align)).collect();
+        table.headers = self.headers.iter().filter(|&&(r, c)| r >= row && r - row < rows && c >= col && c - col < cols).map(|&(r, c)| (r - row, c - col)).collect();
+        table
+    }
+    /// Splits the table into pages of `rows_per_page` rows, the last page holding the remainder.
+    /// Spans crossing a page break are clipped like in `subtable`: the part on the anchor's page is kept
+    /// and the shadows on the following page become empty.
+    /// Panics if `rows_per_page` is 0.
+    pub fn paginate_rows(&self, rows_per_page: u32) -> impl Iterator<Item=Table<T>> + '_ where T: Clone {
+        assert!(rows_per_page > 0, "rows_per_page must not be zero");
+        (0 .. self.num_rows.div_ceil(rows_per_page)).map(move |page| {
+            let row = page * rows_per_page;
+            self.subtable(row, 0, rows_per_page.min(self.num_rows - row), self.num_cols)
+        })
+    }
+    /// Mirrors the columns. Anchors stay at the top-left of their span.
+    pub fn flip_horizontal(&mut self) {
+        let (rows, cols) = (self.num_rows, self.num_cols);
+        let mut table = std::mem::take(self);
+        let alignments = std::mem::take(&mut table.alignments);
+        *self = table.remap(rows, cols, |row, col, rowspan, colspan| (row, cols - col - colspan, rowspan, colspan));
+        self.alignments = alignments;
+        self.remap_alignments(|col| Some(cols - 1 - col));
+    }
+    /// Mirrors the rows. Anchors stay at the top-left of their span.
+    pub fn flip_vertical(&mut self) {
+        let (rows, cols) = (self.num_rows, self.num_cols);
+        let mut table = std::mem::take(self);
+        let alignments = std::mem::take(&mut table.alignments);
+        *self = table.remap(rows, cols, |row, col, rowspan, colspan| (rows - row - rowspan, col, rowspan, colspan));
+        self.alignments = alignments;
+    }
+    /// Moves every occupied cell to the span `f(row, col, rowspan, colspan)` of a new `rows` x `cols` table,
+    /// re-deriving the shadows from scratch.
+    fn remap(self, rows: u32, cols: u32, f: impl Fn(u32, u32, u32, u32) -> (u32, u32, u32, u32)) -> Table<T> {
+        let mut table = Table::empty(rows, cols);
+        let old_cols = self.num_cols.max(1) as usize;
+        for (i, cell) in self.cells.into_iter().enumerate() {
+            if let Cell::Occupied { value, rowspan, colspan } = cell {
+                let (old_row, old_col) = ((i / old_cols) as u32, (i % old_cols) as u32);
+                let (row, col, rowspan, colspan) = f(old_row, old_col, rowspan, colspan);
+                table.set(row, col, Cell::Occupied { value, rowspan, colspan });
+                table.fill_span(row, col, rowspan, colspan);
+                if self.headers.binary_search(&(old_row, old_col)).is_ok() {
+                    table.headers.push((row, col));
+                }
+            }
+        }
+        table.headers.sort_unstable();
+        table
+    }
+    pub fn flat_map<U>(&self, mut f: impl FnMut(&T) -> Option<U>) -> Table<U> {
+        let mut deleted = HashSet::new();
+        let mut table = Table {
+            num_cols: self.num_cols,
+            num_rows: self.num_rows,
+            cells: self.cells_iter().map(|(row, col, cell)| match *cell {
+                Cell::Empty => Cell::Empty,
+                Cell::Occupied { ref value, colspan, rowspan } => match f(value) {
+                    Some(value) => Cell::Occupied { value, colspan, rowspan },
+                    None => {
+                        if colspan != 1 || rowspan != 1 {
+                            deleted.insert((col, row));
+                        }
+                        Cell::Empty
+                    }
+                }
+                Cell::Shadowed { col, row } if deleted.contains(&(col, row)) => Cell::Empty,
+                Cell::Shadowed { col, row } => Cell::Shadowed { col, row },
+            }).collect(),
+            alignments: self.alignments.clone(),
+            headers: self.headers.clone()
+        };
+        table.remap_headers(|row, col| Some((row, col)));
+        table
+    }
+    /// Clears every occupied cell for which `pred(row, col, value)` is false, together with its shadows.
+    pub fn retain(&mut self, pred: impl Fn(u32, u32, &T) -> bool) {
+        let mut deleted = HashSet::new();
+        for (row, col, cell) in self.cells_iter_mut() {
+            match *cell {
+                Cell::Occupied { ref value, colspan, rowspan } if !pred(row, col, value) => {
+                    if colspan != 1 || rowspan != 1 {
+                        deleted.insert((col, row));
+                    }
+                    *cell = Cell::Empty;
+                }
+                Cell::Shadowed { col, row } if deleted.contains(&(col, row)) => *cell = Cell::Empty,
+                _ => {}
+            }
+        }
+        self.remap_headers(|row, col| Some((row, col)));
+    }
+    pub fn values(&self) -> impl Iterator<Item=CellValue<'_, T>> {
+        self.cells_iter().flat_map(|(row, col, cell)| match *cell {
+            Cell::Occupied { ref value, colspan, rowspan } => Some(CellValue {
+                value,
+                row, col,
+                rowspan, colspan
+            }),
+            _ => None
+        })
+    }
+    /// Whether any occupied cell holds `value`. Empty and shadowed coordinates never match.
+    pub fn contains(&self, value: &T) -> bool where T: PartialEq {
+        self.values().any(|cell| cell.value == value)
+    }
+    /// Counts occupied cells by `key(value)`.
+    pub fn count_by<K: Eq + Hash>(&self, key: impl Fn(&T) -> K) -> HashMap<K, usize> {
+        self.values().fold(HashMap::new(), |mut counts, cell| {
+            *counts.entry(key(cell.value)).or_insert(0) += 1;
+            counts
+        })
+    }
+    /// Coordinates of the first occupied cell, in row-major order, whose value satisfies `pred`.
+    pub fn find(&self, pred: impl Fn(&T) -> bool) -> Option<(u32, u32)> {
+        self.find_all(pred).next()
+    }
+    pub fn find_all<'a>(&'a self, pred: impl Fn(&T) -> bool + 'a) -> impl Iterator<Item=(u32, u32)> + 'a {
+        self.values().filter(move |cell| pred(cell.value)).map(|cell| (cell.row, cell.col))
+    }
+    /// Inclusive `(min_row, min_col, max_row, max_col)` over the full extent of all occupied cells.
+    pub fn content_bounds(&self) -> Option<(u32, u32, u32, u32)> {
+        self.values().fold(None, |bounds, cell| {
+            let (min_row, min_col, max_row, max_col) = (cell.row, cell.col, cell.row + cell.rowspan - 1, cell.col + cell.colspan - 1);
+            Some(match bounds {
+                None => (min_row, min_col, max_row, max_col),
+                Some((a, b, c, d)) => (a.min(min_row), b.min(min_col), c.max(max_row), d.max(max_col))
+            })
+        })
+    }
+    /// Visits every coordinate in reading order. Occupied anchors yield `Some(value)`,
+    /// empty and shadowed coordinates `None`.
+    pub fn iter(&self) -> impl Iterator<Item=(u32, u32, Option<&T>)> {
+        self.cells_iter().map(|(row, col, cell)| match *cell {
+            Cell::Occupied { ref value, .. } => (row, col, Some(value)),
+            _ => (row, col, None)
+        })
+    }
+    /// Walks the grid in reading order, yielding every anchor once with its span and every empty cell.
+    /// Shadows are skipped.
+    pub fn visual_cells(&self) -> impl Iterator<Item=VisualCell<'_, T>> {
+        self.cells_iter().filter_map(|(row, col, cell)| match *cell {
+            Cell::Occupied { ref value, colspan, rowspan } => Some(VisualCell::Occupied { value, row, col, rowspan, colspan }),
+            Cell::Empty => Some(VisualCell::Empty { row, col }),
+            Cell::Shadowed { .. } => None
+        })
+    }
+    pub fn empties(&self) -> impl Iterator<Item=(u32, u32)> + '_ {
+        self.cells_iter().filter(|(_, _, cell)| cell.is_empty()).map(|(row, col, _)| (row, col))
+    }
+    /// Yields `(shadow_row, shadow_col, anchor_row, anchor_col)` for every shadowed coordinate.
+    pub fn shadows(&self) -> impl Iterator<Item=(u32, u32, u32, u32)> + '_ {
+        self.cells_iter().filter_map(|(row, col, cell)| match *cell {
+            Cell::Shadowed { col: anchor_col, row: anchor_row } => Some((row, col, anchor_row, anchor_col)),
+            _ => None
+        })
+    }
+    pub fn values_mut(&mut self) -> impl Iterator<Item=CellValueMut<'_, T>> {
+        self.cells_iter_mut().flat_map(|(row, col, cell)| match *cell {
+            Cell::Occupied { ref mut value, colspan, rowspan } => Some(CellValueMut {
+                value,
+                row, col,
+                rowspan, colspan
+            }),
+            _ => None
+        })
+    }
+    /// Yields every cell in column-major order, the counterpart to the row-major storage order.
+    pub fn cells_by_column(&self) -> impl Iterator<Item=(u32, u32, &Cell<T>)> {
+        (0 .. self.num_cols).flat_map(move |col|
+            self.column(col).unwrap().enumerate().map(move |(row, cell)| (row as u32, col, cell))
+        )
+    }
+    fn cells_iter(&self) -> impl Iterator<Item=(u32, u32, &Cell<T>)> {
+        self.cells.chunks_exact(self.num_cols.max(1) as usize).enumerate().
+        flat_map(|(row, cells)|
+            cells.iter().enumerate().map(move |(col, cell)| (row as u32, col as u32, cell))
+        )
+    }
+    fn cells_iter_mut(&mut self) -> impl Iterator<Item=(u32, u32, &mut Cell<T>)> {
+        self.cells.chunks_exact_mut(self.num_cols.max(1) as usize).enumerate().
+        flat_map(|(row, cells)|
+            cells.iter_mut().enumerate().map(move |(col, cell)| (row as u32, col as u32, cell))
+        )
+    }
+}
+impl Table<String> {
+    /// Renders via `format_html`, escaping every value with `escape_html`.
+    pub fn to_html_escaped(&self) -> String {
+        let mut out = String::new();
+        self.format_html(&mut out, |w, value| escape_html(value, w)).unwrap();
+        out
+    }
+}
+#[cfg(feature="csv")]
+impl Table<String> {
+    /// Reads every record into a row of 1x1 cells, empty fields become `Cell::Empty`.
+    /// Whether the first line is a header, and whether records may differ in length,
+    /// is up to how `reader` was configured. Short rows are padded.
+    pub fn from_csv_reader<R: std::io::Read>(reader: &mut csv::Reader<R>) -> Result<Table<String>, csv::Error> {
+        let mut table = Table::new();
+        for record in reader.records() {
+            let record = record?;
+            table.push_row(record.iter().map(|field| (!field.is_empty()).then(|| field.to_string())).collect());
+        }
+        Ok(table)
+    }
+}
+/// Byte offset just past the opening quote of a `style` attribute in `attrs`.
+fn style_value_start(attrs: &str) -> Option<usize> {
+    attrs.match_indices("style=").find_map(|(i, _)| {
+        let at_word_start = attrs[.. i].chars().next_back().is_none_or(char::is_whitespace);
+        let quoted = matches!(attrs[i + 6 ..].chars().next(), Some('"' | '\''));
+        (at_word_start && quoted).then_some(i + 7)
+    })
+}
+pub fn escape_html(s: &str, out: &mut impl fmt::Write) -> fmt::Result {
+    for c in s.chars() {
+        match c {
+            '<' => out.write_str("&lt;")?,
+            '>' => out.write_str("&gt;")?,
+            '&' => out.write_str("&amp;")?,
+            '"' => out.write_str("&quot;")?,
+            '\'' => out.write_str("&#39;")?,
+            c => out.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+/// Zero-copy access to an archived table, using the same row-major layout.
+#[cfg(feature="rkyv")]
+impl<T: rkyv::Archive> ArchivedTable<T> {
+    pub fn num_rows(&self) -> u32 {
+        self.num_rows.to_native()
+    }
+    pub fn num_cols(&self) -> u32 {
+        self.num_cols.to_native()
+    }
+    pub fn get_cell(&self, row: u32, col: u32) -> Option<&ArchivedCell<T>> {
+        if row >= self.num_rows() || col >= self.num_cols() {
+            return None;
+        }
+        self.cells.get(self.num_cols() as usize * row as usize + col as usize)
+    }
+}
+/// Prints the dimensions and one line per row, with `.` for empty cells,
+/// `^(row,col)` for shadows pointing at their anchor and the `Debug` output of occupied values.
+/// Columns are padded to a common width.
+impl<T: fmt::Debug> fmt::Debug for Table<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let texts: Vec<String> = self.cells.iter().map(|cell| match *cell {
+            Cell::Empty => ".".into(),
+            Cell::Occupied { ref value, .. } => format!("{:?}", value),
+            Cell::Shadowed { col, row } => format!("^({row},{col})"),
+        }).collect();
+        let mut widths = vec![0; self.num_cols as usize];
+        for (i, text) in texts.iter().enumerate() {
+            let col = i % self.num_cols as usize;
+            widths[col] = widths[col].max(text.chars().count());
+        }
+        writeln!(f, "Table {}x{} [", self.num_rows, self.num_cols)?;
+        for row in texts.chunks_exact(self.num_cols.max(1) as usize) {
+            let line: String = row.iter().zip(widths.iter()).map(|(text, &width)| format!(" {:<width$}", text)).collect();
+            writeln!(f, "   {}", line.trim_end())?;
+        }
+        write!(f, "]")?;
+        if !self.alignments.is_empty() {
+            write!(f, " alignments: {:?}", self.alignments)?;
+        }
+        if !self.headers.is_empty() {
+            write!(f, " headers: {:?}", self.headers)?;
+        }
+        Ok(())
+    }
+}
+/// Draws the table with `format_box`.
+impl<T: fmt::Display> fmt::Display for Table<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use fmt::Write;
+        self.format_box(f, |w, value| write!(w, "{}", value))
+    }
+}
+/// Number of cells in a `rows` x `cols` table, or `None` if that overflows `usize`.
+fn area(rows: u32, cols: u32) -> Option<usize> {
+    (rows as usize).checked_mul(cols as usize)
+}
+impl<T> Default for Table<T> {
+    fn default() -> Self {
+        Table::new()
+    }
+}
+#[cfg(feature="rayon")]
+impl<T: Send> Table<T> {
+    pub fn par_map<U: Send>(self, f: impl Fn(T) -> U + Sync + Send) -> Table<U> {
+        use rayon::prelude::*;
+        Table {
+            num_cols: self.num_cols,
+            num_rows: self.num_rows,
+            cells: self.cells.into_par_iter().map(|cell| match cell {
+                Cell::Empty => Cell::Empty,
+                Cell::Occupied { value, colspan, rowspan } => Cell::Occupied { value: f(value), colspan, rowspan },
+                Cell::Shadowed { col, row } => Cell::Shadowed { col, row }
+            }).collect(),
+            alignments: self.alignments,
+            headers: self.headers
+        }
+    }
+}
+#[cfg(feature="json")]
+impl<T: serde::Serialize> Table<T> {
+    /// Builds an array of rows like `serde_grid`, but straight into a `serde_json::Value`.
+    /// Anchors hold their serialized value, empty and shadowed coordinates are `null`. Spans are not included.
+    pub fn to_json_value(&self) -> Result<serde_json::Value, serde_json::Error> {
+        use serde_json::Value;
+        let rows = (0 .. self.num_rows).map(|row| {
+            let cells = self.row(row).unwrap().iter().map(|cell| match *cell {
+                Cell::Occupied { ref value, .. } => serde_json::to_value(value),
+                _ => Ok(Value::Null)
+            }).collect::<Result<_, _>>()?;
+            Ok(Value::Array(cells))
+        }).collect::<Result<_, _>>()?;
+        Ok(Value::Array(rows))
+    }
+}
+/// Collects `(row, col, value)` items into 1x1 cells; on duplicate coordinates the last one wins.
+/// The items are buffered once to find the dimensions before the grid is allocated.
+/// Panics like `Table::empty` if a coordinate is `u32::MAX` or the grid is too large.
+impl<T> FromIterator<(u32, u32, T)> for Table<T> {
+    fn from_iter<I: IntoIterator<Item=(u32, u32, T)>>(iter: I) -> Self {
+        let items: Vec<_> = iter.into_iter().collect();
+        let max_row = items.iter().map(|&(row, _, _)| row).max();
+        let max_col = items.iter().map(|&(_, col, _)| col).max();
+        // one past the largest coordinate, which does not fit if that coordinate is u32::MAX
+        let end = |max: Option<u32>| max.map_or(Some(0), |max| max.checked_add(1));
+        let (Some(num_rows), Some(num_cols)) = (end(max_row), end(max_col)) else {
+            panic!("{}", Error::TooLarge { rows: max_row.unwrap_or(0).saturating_add(1), cols: max_col.unwrap_or(0).saturating_add(1) });
+        };
+        let mut table = Table::empty(num_rows, num_cols);
+        for (row, col, value) in items {
+            table.set(row, col, Cell::Occupied { value, colspan: 1, rowspan: 1 });
+        }
+        table
+    }
+}
+/// Indexing by `(row, col)` yields the raw `Cell`, not the inner value. Use `get_cell_value` for that.
+impl<T> Index<(u32, u32)> for Table<T> {
+    type Output = Cell<T>;
+    fn index(&self, (row, col): (u32, u32)) -> &Cell<T> {
+        match self.get_cell(row, col) {
+            Some(cell) => cell,
+            None => panic!("cell row={row}, col={col} out of bounds")
+        }
+    }
+}
+impl<T> IndexMut<(u32, u32)> for Table<T> {
+    fn index_mut(&mut self, (row, col): (u32, u32)) -> &mut Cell<T> {
+        match self.get_cell_mut(row, col) {
+            Some(cell) => cell,
+            None => panic!("cell row={row}, col={col} out of bounds")
+        }
+    }
+}
+/// Read-only access to a table, e.g. for rendering code that must not modify it.
+/// Holds a shared borrow of the table for as long as it lives.
+pub struct TableView<'a, T> {
+    table: &'a Table<T>,
+}
+impl<T> Clone for TableView<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for TableView<'_, T> {}
+impl<'a, T> TableView<'a, T> {
+    pub fn num_rows(&self) -> u32 {
+        self.table.num_rows()
+    }
+    pub fn num_cols(&self) -> u32 {
+        self.table.num_cols()
+    }
+    pub fn get_cell(&self, row: u32, col: u32) -> Option<&'a Cell<T>> {
+        self.table.get_cell(row, col)
+    }
+    pub fn get_cell_value(&self, row: u32, col: u32) -> Option<&'a T> {
+        self.table.get_cell_value(row, col)
+    }
+    pub fn row(&self, row: u32) -> Option<&'a [Cell<T>]> {
+        self.table.row(row)
+    }
+    pub fn values(&self) -> impl Iterator<Item=CellValue<'a, T>> {
+        self.table.values()
+    }
+}
+/// Bridges an `io::Write` into `fmt::Write`, so any of the `format_*` methods can stream to it.
+/// `fmt::Write` cannot carry the underlying error, so the first one is kept until `finish`.
+pub struct IoWriter<W> {
+    inner: W,
+    error: Option<io::Error>,
+}
+impl<W: io::Write> IoWriter<W> {
+    pub fn new(inner: W) -> Self {
+        IoWriter { inner, error: None }
+    }
+    /// Turns the result of a `format_*` call into an `io::Result`, reporting the io error behind a `fmt::Error`.
+    pub fn finish(self, result: fmt::Result) -> io::Result<W> {
+        match (result, self.error) {
+            (_, Some(e)) => Err(e),
+            (Err(fmt::Error), None) => Err(io::Error::other("formatting a cell failed")),
+            (Ok(()), None) => Ok(self.inner),
+        }
+    }
+}
+impl<W: io::Write> fmt::Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+/// Collects rows for `Table::from_rows`. The table is as wide as the widest row pushed.
+pub struct RowBuilder<T> {
+    rows: Vec<Vec<Option<T>>>,
+}
+impl<T> RowBuilder<T> {
+    pub fn push_row(&mut self, cells: Vec<Option<T>>) {
+        self.rows.push(cells);
+    }
+    pub fn finish(self) -> Table<T> {
+        Table::from_rows(self.rows)
+    }
+}
+pub struct CellValue<'a, T> {
+    pub value: &'a T,
+    pub col: u32,
+    pub row: u32,
+    pub colspan: u32,
+    pub rowspan: u32,
+}
+/// Item of `Table::visual_cells`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum VisualCell<'a, T> {
+    Occupied { value: &'a T, row: u32, col: u32, rowspan: u32, colspan: u32 },
+    Empty { row: u32, col: u32 },
+}
+/// Returned by `Table::cell_stats`. The counts add up to `num_rows * num_cols`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CellStats {
+    pub empty: usize,
+    pub occupied: usize,
+    pub shadowed: usize,
+}
+pub struct CellValueMut<'a, T> {
+    pub value: &'a mut T,
+    pub col: u32,
+    pub row: u32,
+    pub colspan: u32,
+    pub rowspan: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write as _;
+
+    #[test]
+    fn anchor_of_resolves_shadows() {
+        let mut table = Table::empty(4, 4);
+        table.set_cell("a", 1, 1, 3, 3).unwrap();
+        for row in 1 .. 4 {
+            for col in 1 .. 4 {
+                assert_eq!(table.anchor_of(row, col), Some((1, 1)));
+                let (r, c) = table.anchor_of(row, col).unwrap();
+                assert_eq!(table.get_cell_value(r, c), Some(&"a"));
+            }
+        }
+        assert_eq!(table.anchor_of(0, 0), None);
+        assert_eq!(table.anchor_of(0, 1), None);
+        assert_eq!(table.anchor_of(4, 1), None);
+        assert_eq!(table.anchor_of(1, 4), None);
+    }
+
+    #[test]
+    fn remove_cell_clears_shadows() {
+        let mut table = Table::empty(3, 4);
+        table.set_cell(1, 0, 1, 2, 3).unwrap();
+        table.set_cell(2, 2, 0, 1, 1).unwrap();
+        assert_eq!(table.remove_cell(1, 3), Some(1));
+        assert!(table.shadows().next().is_none());
+        assert_eq!(table.cell_stats(), CellStats { empty: 11, occupied: 1, shadowed: 0 });
+        assert_eq!(table.remove_cell(0, 1), None);
+        assert_eq!(table.remove_cell(2, 0), Some(2));
+        assert!(table.cells.iter().all(Cell::is_empty));
+    }
+
+
+    fn merged_2x2() -> Table<String> {
+        let mut table = Table::empty(3, 3);
+        table.set_cell("a,b".to_string(), 0, 0, 2, 2).unwrap();
+        table.set_cell("say \"hi\"".to_string(), 0, 2, 1, 1).unwrap();
+        table.set_cell("c".to_string(), 2, 1, 1, 1).unwrap();
+        table
+    }
+
+    #[test]
+    fn format_csv_merged() {
+        let mut out = String::new();
+        merged_2x2().format_csv(&mut out, |w, s| w.write_str(s)).unwrap();
+        assert_eq!(out, "\"a,b\",,\"say \"\"hi\"\"\"\n,,\n,c,\n");
+    }
+
+    #[cfg(feature="csv")]
+    #[test]
+    fn format_csv_round_trip() {
+        let table = merged_2x2();
+        let mut out = String::new();
+        table.format_csv(&mut out, |w, s| w.write_str(s)).unwrap();
+        let mut reader = csv::ReaderBuilder::new().has_headers(false).from_reader(out.as_bytes());
+        let read = Table::from_csv_reader(&mut reader).unwrap();
+        assert_eq!(read.size(), (3, 3));
+        assert_eq!(read.into_rows(), table.into_rows());
+    }
+
+
+    #[test]
+    fn transpose_1x1() {
+        let table = Table::from_rows(vec![
+            vec![Some(1), Some(2), None],
+            vec![None, Some(5), Some(6)],
+        ]);
+        let transposed = table.transpose();
+        assert_eq!(transposed.size(), (3, 2));
+        assert_eq!(transposed.into_rows(), vec![
+            vec![Some(1), None],
+            vec![Some(2), Some(5)],
+            vec![None, Some(6)],
+        ]);
+    }
+
+    #[test]
+    fn transpose_span() {
+        let mut table = Table::empty(3, 4);
+        table.set_cell('x', 1, 0, 2, 3).unwrap();
+        table.set_cell('y', 0, 3, 1, 1).unwrap();
+        let transposed = table.transpose();
+        assert_eq!(transposed.size(), (4, 3));
+        assert_eq!(transposed.span_at(0, 1), Some((3, 2)));
+        assert_eq!(transposed.get_cell_value(3, 0), Some(&'y'));
+        for row in 0 .. 3 {
+            for col in 1 .. 3 {
+                assert_eq!(transposed.anchor_of(row, col), Some((0, 1)));
+            }
+        }
+        assert!(transposed.validate().is_ok());
+    }
+
+
+    #[test]
+    fn insert_row_extends_crossing_span() {
+        let mut table = Table::empty(4, 3);
+        table.set_cell("span", 0, 0, 3, 2).unwrap();
+        table.set_cell("below", 3, 0, 1, 1).unwrap();
+        table.set_cell("tail", 2, 2, 2, 1).unwrap();
+        table.insert_row(1);
+        assert_eq!(table.size(), (5, 3));
+        assert_eq!(table.span_at(0, 0), Some((4, 2)));
+        for row in 0 .. 4 {
+            for col in 0 .. 2 {
+                assert_eq!(table.anchor_of(row, col), Some((0, 0)));
+            }
+        }
+        assert_eq!(table[(1, 2)], Cell::Empty);
+        assert_eq!(table.get_cell_value(4, 0), Some(&"below"));
+        assert_eq!(table.get_cell_value(3, 2), Some(&"tail"));
+        assert_eq!(table[(4, 2)], Cell::Shadowed { col: 2, row: 3 });
+        assert!(table.validate().is_ok());
+    }
+
+    #[test]
+    fn insert_row_at_span_start_and_end() {
+        let mut table = Table::empty(2, 1);
+        table.set_cell(1, 0, 0, 2, 1).unwrap();
+        table.insert_row(0);
+        assert_eq!(table[(0, 0)], Cell::Empty);
+        assert_eq!(table.span_at(1, 0), Some((2, 1)));
+        table.insert_row(3);
+        assert_eq!(table.size(), (4, 1));
+        assert_eq!(table[(3, 0)], Cell::Empty);
+        assert_eq!(table.span_at(1, 0), Some((2, 1)));
+        assert!(table.validate().is_ok());
+    }
+
+
+    #[test]
+    fn delete_row_shrinks_crossing_span() {
+        let mut table = Table::empty(4, 2);
+        table.set_cell("span", 0, 0, 3, 1).unwrap();
+        table.set_cell("tail", 2, 1, 2, 1).unwrap();
+        table.delete_row(1);
+        assert_eq!(table.size(), (3, 2));
+        assert_eq!(table.span_at(0, 0), Some((2, 1)));
+        assert_eq!(table[(1, 0)], Cell::Shadowed { col: 0, row: 0 });
+        assert_eq!(table.get_cell_value(1, 1), Some(&"tail"));
+        assert_eq!(table[(2, 1)], Cell::Shadowed { col: 1, row: 1 });
+        assert!(table.validate().is_ok());
+    }
+
+    #[test]
+    fn delete_row_moves_anchor_down() {
+        let mut table = Table::empty(3, 3);
+        table.set_cell("span", 0, 0, 3, 2).unwrap();
+        table.set_cell("single", 0, 2, 1, 1).unwrap();
+        table.delete_row(0);
+        assert_eq!(table.size(), (2, 3));
+        assert_eq!(table.get_cell_value(0, 0), Some(&"span"));
+        assert_eq!(table.span_at(0, 0), Some((2, 2)));
+        for row in 0 .. 2 {
+            for col in 0 .. 2 {
+                assert_eq!(table.anchor_of(row, col), Some((0, 0)));
+            }
+        }
+        assert_eq!(table[(0, 2)], Cell::Empty);
+        assert!(!table.contains(&"single"));
+        assert!(table.validate().is_ok());
+    }
+
+    #[test]
+    fn delete_row_drops_header_of_removed_anchor() {
+        let mut table = Table::empty(3, 1);
+        table.set_cell(1, 1, 0, 1, 1).unwrap();
+        table.set_cell(2, 2, 0, 1, 1).unwrap();
+        table.set_header(1, 0, true).unwrap();
+        table.delete_row(1);
+        assert_eq!(table.get_cell_value(1, 0), Some(&2));
+        assert!(!table.is_header(1, 0));
+    }
+
+
+    #[test]
+    fn insert_column_extends_crossing_span() {
+        let mut table = Table::empty(2, 4);
+        table.set_cell("wide", 0, 0, 1, 3).unwrap();
+        table.set_cell("right", 1, 3, 1, 1).unwrap();
+        table.set_column_alignment(3, Alignment::Right);
+        table.insert_column(2);
+        assert_eq!(table.size(), (2, 5));
+        assert_eq!(table.span_at(0, 0), Some((1, 4)));
+        for col in 1 .. 4 {
+            assert_eq!(table[(0, col)], Cell::Shadowed { col: 0, row: 0 });
+        }
+        assert_eq!(table[(1, 2)], Cell::Empty);
+        assert_eq!(table.get_cell_value(1, 4), Some(&"right"));
+        assert_eq!(table.column_alignment(4), Alignment::Right);
+        assert!(table.validate().is_ok());
+    }
+
+    #[test]
+    fn insert_column_at_edges() {
+        let mut table = Table::empty(1, 2);
+        table.set_cell(1, 0, 0, 1, 2).unwrap();
+        table.insert_column(0);
+        table.insert_column(3);
+        assert_eq!(table.size(), (1, 4));
+        assert_eq!(table.span_at(0, 1), Some((1, 2)));
+        assert_eq!(table[(0, 0)], Cell::Empty);
+        assert_eq!(table[(0, 3)], Cell::Empty);
+        assert!(table.validate().is_ok());
+    }
+
+    #[test]
+    fn delete_column_shrinks_and_moves_anchor() {
+        let mut table = Table::empty(2, 4);
+        table.set_cell("wide", 0, 0, 2, 3).unwrap();
+        table.set_cell("right", 0, 3, 1, 1).unwrap();
+        table.delete_column(1);
+        assert_eq!(table.size(), (2, 3));
+        assert_eq!(table.span_at(0, 0), Some((2, 2)));
+        assert_eq!(table.get_cell_value(0, 2), Some(&"right"));
+        assert!(table.validate().is_ok());
+
+        table.delete_column(0);
+        assert_eq!(table.size(), (2, 2));
+        assert_eq!(table.get_cell_value(0, 0), Some(&"wide"));
+        assert_eq!(table.span_at(1, 0), Some((2, 1)));
+        assert_eq!(table.get_cell_value(0, 1), Some(&"right"));
+        assert!(table.validate().is_ok());
+
+        table.delete_column(1);
+        assert!(!table.contains(&"right"));
+        assert!(table.validate().is_ok());
+    }
+
+
+    #[test]
+    fn map_ref_keeps_original() {
+        let mut table = Table::empty(2, 3);
+        table.set_cell(4, 0, 0, 2, 2).unwrap();
+        table.set_cell(7, 1, 2, 1, 1).unwrap();
+        let strings = table.map_ref(|n| n.to_string());
+        assert_eq!(strings.size(), table.size());
+        assert_eq!(strings.span_at(1, 1), Some((2, 2)));
+        assert_eq!(strings[(1, 0)], Cell::Shadowed { col: 0, row: 0 });
+        assert_eq!(strings[(0, 2)], Cell::Empty);
+        assert_eq!(strings.get_cell_value(1, 2).map(String::as_str), Some("7"));
+        table.set_cell(9, 0, 2, 1, 1).unwrap();
+        assert_eq!(table.values().map(|cell| *cell.value).sum::<i32>(), 20);
+    }
+
+
+    #[test]
+    fn try_map_ok() {
+        let mut table = Table::empty(2, 2);
+        table.set_cell("12", 0, 0, 1, 2).unwrap();
+        table.set_cell("3", 1, 1, 1, 1).unwrap();
+        let parsed = table.try_map(|s| s.parse::<u32>()).unwrap();
+        assert_eq!(parsed.get_cell_value(0, 0), Some(&12));
+        assert_eq!(parsed.get_cell_value(1, 1), Some(&3));
+        assert_eq!(parsed[(0, 1)], Cell::Shadowed { col: 0, row: 0 });
+        assert_eq!(parsed[(1, 0)], Cell::Empty);
+    }
+
+    #[test]
+    fn try_map_stops_at_first_error() {
+        let table = Table::from_rows(vec![vec![Some("1"), Some("x"), Some("2"), Some("y")]]);
+        let mut calls = 0;
+        let result = table.try_map(|s| {
+            calls += 1;
+            s.parse::<u32>().map_err(|_| s)
+        });
+        assert_eq!(result.unwrap_err(), "x");
+        assert_eq!(calls, 2);
+    }
+
+
+    #[test]
+    fn column_strides_non_square() {
+        let table = Table::from_rows(vec![
+            vec![Some(0), Some(1), Some(2)],
+            vec![Some(3), None, Some(5)],
+        ]);
+        let values = |col| table.column(col).unwrap().map(|cell| match *cell {
+            Cell::Occupied { value, .. } => Some(value),
+            _ => None
+        }).collect::<Vec<_>>();
+        assert_eq!(values(0), vec![Some(0), Some(3)]);
+        assert_eq!(values(1), vec![Some(1), None]);
+        assert_eq!(values(2), vec![Some(2), Some(5)]);
+        assert!(table.column(3).is_none());
+
+        let tall = Table::from_rows(vec![vec![Some(0), Some(1)], vec![Some(2), Some(3)], vec![Some(4), Some(5)]]);
+        assert_eq!(tall.column(1).unwrap().count(), 3);
+
+        let flat = Table::<i32>::empty(0, 3);
+        assert_eq!(flat.column(2).unwrap().count(), 0);
+        assert!(flat.column(3).is_none());
+    }
+
+
+    fn numbered(rows: u32, cols: u32) -> Table<u32> {
+        Table::from_rows((0 .. rows).map(|r| (0 .. cols).map(|c| Some(r * 10 + c)).collect()).collect())
+    }
+
+    #[test]
+    fn resize_grow_cols() {
+        let mut table = numbered(2, 2);
+        table.resize(2, 4);
+        assert_eq!(table.into_rows(), vec![vec![Some(0), Some(1), None, None], vec![Some(10), Some(11), None, None]]);
+    }
+
+    #[test]
+    fn resize_shrink_cols() {
+        let mut table = numbered(2, 3);
+        table.resize(2, 1);
+        assert_eq!(table.into_rows(), vec![vec![Some(0)], vec![Some(10)]]);
+    }
+
+    #[test]
+    fn resize_grow_rows() {
+        let mut table = numbered(1, 2);
+        table.resize(3, 2);
+        assert_eq!(table.into_rows(), vec![vec![Some(0), Some(1)], vec![None, None], vec![None, None]]);
+    }
+
+    #[test]
+    fn resize_shrink_rows() {
+        let mut table = numbered(3, 2);
+        table.resize(1, 2);
+        assert_eq!(table.into_rows(), vec![vec![Some(0), Some(1)]]);
+    }
+
+    #[test]
+    fn resize_truncates_spans() {
+        let mut table = Table::empty(3, 3);
+        table.set_cell(1, 1, 1, 2, 2).unwrap();
+        table.resize(2, 2);
+        assert_eq!(table.span_at(1, 1), Some((1, 1)));
+        assert!(table.validate().is_ok());
+    }
+
+
+    #[test]
+    fn from_iter_dimensions() {
+        let table: Table<char> = [(0, 0, 'a'), (2, 4, 'b'), (1, 1, 'c'), (2, 4, 'd')].into_iter().collect();
+        assert_eq!(table.size(), (3, 5));
+        assert_eq!(table.get_cell_value(2, 4), Some(&'d'));
+        assert_eq!(table.occupied_count(), 3);
+
+        let empty: Table<char> = std::iter::empty().collect();
+        assert_eq!(empty.size(), (0, 0));
+    }
+
+
+    #[test]
+    #[should_panic(expected = "too large")]
+    fn from_iter_max_coordinate() {
+        let _: Table<char> = [(0, u32::MAX, 'a')].into_iter().collect();
+    }
+
+
+    #[cfg(feature="rayon")]
+    #[test]
+    fn par_map_matches_map() {
+        let mut table = Table::empty(50, 40);
+        table.set_cell(0, 0, 0, 3, 3).unwrap();
+        for i in 1 .. 1000 {
+            table.set_cell(i, 3 + i / 40 % 47, i % 40, 1, 1).unwrap();
+        }
+        assert_eq!(table.clone().par_map(|n| n * 2), table.map(|n| n * 2));
+    }
+
+
+    #[test]
+    fn format_latex_spans() {
+        let mut table = Table::empty(3, 3);
+        table.set_cell("a", 0, 0, 1, 2).unwrap();
+        table.set_cell("b", 0, 2, 2, 1).unwrap();
+        table.set_cell("c", 1, 0, 1, 1).unwrap();
+        table.set_cell("d", 1, 1, 1, 1).unwrap();
+        table.set_cell("e", 2, 0, 1, 3).unwrap();
+        let mut out = String::new();
+        table.format_latex(&mut out, |w, s| w.write_str(s)).unwrap();
+        assert_eq!(out, r"\begin{tabular}{ccc}
+\multicolumn{2}{c}{a} & \multirow{2}{*}{b} \\
+c & d &  \\
+\multicolumn{3}{c}{e} \\
+\end{tabular}
+");
+    }
+
+
+    #[test]
+    fn format_box_span() {
+        let mut table = Table::empty(2, 2);
+        table.set_cell("wide", 0, 0, 1, 2).unwrap();
+        table.set_cell("a", 1, 0, 1, 1).unwrap();
+        table.set_cell("bb", 1, 1, 1, 1).unwrap();
+        let mut out = String::new();
+        table.format_box(&mut out, |w, s| w.write_str(s)).unwrap();
+        assert_eq!(out, "\
+┌────────┐
+│ wide   │
+├───┬────┤
+│ a │ bb │
+└───┴────┘
+");
+    }
+
+
+    #[test]
+    fn format_html_with_header_span() {
+        let mut table = Table::empty(3, 2);
+        table.set_cell("head", 0, 0, 1, 2).unwrap();
+        table.set_cell("a", 1, 0, 2, 1).unwrap();
+        table.set_cell("b", 1, 1, 1, 1).unwrap();
+        let mut out = String::new();
+        table.format_html_with_header(&mut out, 1, |w, s| w.write_str(s)).unwrap();
+        assert_eq!(out, "\
+<table>
+<thead>
+<tr>
+<th colspan=2>head</th>
+</tr>
+</thead>
+<tbody>
+<tr>
+<td rowspan=2>a</td>
+<td>b</td>
+</tr>
+<tr>
+<td></td></tr>
+</tbody>
+</table>
+");
+
+        let (mut plain, mut zero) = (String::new(), String::new());
+        table.format_html(&mut plain, |w, s| w.write_str(s)).unwrap();
+        table.format_html_with_header(&mut zero, 0, |w, s| w.write_str(s)).unwrap();
+        assert_eq!(plain, zero);
+        assert!(!plain.contains("<thead>") && !plain.contains("<th"));
+    }
+
+
+    #[test]
+    fn format_html_attrs_on_occupied_cells() {
+        let mut table = Table::empty(2, 2);
+        table.set_cell(1, 0, 0, 1, 2).unwrap();
+        table.set_cell(2, 1, 1, 1, 1).unwrap();
+        let mut out = String::new();
+        table.format_html_attrs(&mut out, |w, n| write!(w, "{n}"), |row, col, n| format!("id=\"c{row}{col}\" class=\"n{n}\"")).unwrap();
+        assert_eq!(out, "\
+<table>
+<tbody>
+<tr>
+<td id=\"c00\" class=\"n1\" colspan=2>1</td>
+</tr>
+<tr>
+<td></td><td id=\"c11\" class=\"n2\">2</td>
+</tr>
+</tbody>
+</table>
+");
+    }
+
+
+    #[test]
+    fn validate_ok() {
+        let mut table = Table::empty(3, 3);
+        table.set_cell(1, 0, 0, 2, 2).unwrap();
+        table.set_cell(2, 2, 2, 1, 1).unwrap();
+        assert!(table.validate().is_ok());
+        assert!(Table::<u8>::new().validate().is_ok());
+    }
+
+    #[test]
+    fn validate_corrupt() {
+        let mut base = Table::empty(3, 3);
+        base.set_cell(1, 1, 1, 2, 2).unwrap();
+
+        let mut table = base.clone();
+        table[(0, 0)] = Cell::Shadowed { col: 5, row: 0 };
+        assert!(matches!(table.validate(), Err(ValidationError::ShadowOutOfBounds { row: 0, col: 0, anchor_row: 0, anchor_col: 5 })));
+
+        let mut table = base.clone();
+        table[(0, 1)] = Cell::Shadowed { col: 0, row: 0 };
+        assert!(matches!(table.validate(), Err(ValidationError::ShadowWithoutAnchor { row: 0, col: 1, .. })));
+
+        // shadows above and left of the anchor
+        let mut table = base.clone();
+        table[(0, 1)] = Cell::Shadowed { col: 1, row: 1 };
+        assert!(matches!(table.validate(), Err(ValidationError::ShadowNotCovered { row: 0, col: 1, anchor_row: 1, anchor_col: 1 })));
+        let mut table = base.clone();
+        table[(1, 0)] = Cell::Shadowed { col: 1, row: 1 };
+        assert!(matches!(table.validate(), Err(ValidationError::ShadowNotCovered { row: 1, col: 0, .. })));
+
+        let mut table = base.clone();
+        table[(1, 2)] = Cell::Empty;
+        assert!(matches!(table.validate(), Err(ValidationError::MissingShadow { row: 1, col: 2, anchor_row: 1, anchor_col: 1 })));
+
+        let mut table = base.clone();
+        table[(0, 0)] = Cell::Occupied { value: 2, colspan: 0, rowspan: 1 };
+        assert!(matches!(table.validate(), Err(ValidationError::EmptySpan { row: 0, col: 0 })));
+
+        let mut table = base.clone();
+        table[(0, 2)] = Cell::Occupied { value: 2, colspan: 2, rowspan: 1 };
+        assert!(matches!(table.validate(), Err(ValidationError::SpanOutOfBounds { row: 0, col: 2 })));
+        table[(0, 2)] = Cell::Occupied { value: 2, colspan: 1, rowspan: u32::MAX };
+        assert!(matches!(table.validate(), Err(ValidationError::SpanOutOfBounds { row: 0, col: 2 })));
+
+        let mut table = base.clone();
+        table.cells.pop();
+        assert!(matches!(table.validate(), Err(ValidationError::CellCount { rows: 3, cols: 3, len: 8 })));
+    }
+
+
+    #[test]
+    fn set_cell_checked_rejects_overlap() {
+        let mut table = Table::empty(3, 3);
+        table.set_cell_checked('a', 1, 1, 2, 2).unwrap();
+        let before = table.clone();
+        // with `set_cell` these would leave shadows of 'a' pointing at a replaced anchor
+        assert!(matches!(table.set_cell_checked('b', 0, 2, 3, 1), Err(Error::Overlap { col: 2, row: 1 })));
+        assert!(matches!(table.set_cell_checked('b', 0, 0, 2, 2), Err(Error::Overlap { col: 1, row: 1 })));
+        assert!(matches!(table.set_cell_checked('b', 2, 2, 1, 1), Err(Error::Shadowed { col: 1, row: 1 })));
+        assert_eq!(table, before);
+        assert!(table.validate().is_ok());
+
+        // replacing the anchor with a span over its own shadows is fine
+        assert_eq!(table.set_cell_checked('c', 1, 1, 2, 1).unwrap(), Some('a'));
+        assert_eq!(table.set_cell_checked('b', 0, 2, 3, 1).unwrap(), None);
+        assert!(table.validate().is_ok());
+        assert!(matches!(table.set_cell_checked('d', 0, 0, 1, 0), Err(Error::InvalidSpan { .. })));
+    }
+
+
+    #[test]
+    fn set_cell_shrinking_span_leaves_no_shadows() {
+        let mut table = Table::empty(4, 4);
+        table.set_cell(1, 0, 0, 3, 3).unwrap();
+        assert_eq!(table.set_cell(2, 0, 0, 1, 1).unwrap(), Some(1));
+        assert_eq!(table.cell_stats(), CellStats { empty: 15, occupied: 1, shadowed: 0 });
+        assert!(table.validate().is_ok());
+
+        table.set_cell(3, 1, 1, 3, 3).unwrap();
+        table.set_cell(4, 1, 1, 2, 1).unwrap();
+        assert_eq!(table.cell_stats(), CellStats { empty: 13, occupied: 2, shadowed: 1 });
+        assert_eq!(table[(2, 1)], Cell::Shadowed { col: 1, row: 1 });
+        assert!(table.validate().is_ok());
+    }
+
+
+    #[test]
+    fn span_at_covered_coordinates() {
+        let mut table = Table::empty(3, 5);
+        table.set_cell((), 1, 1, 2, 4).unwrap();
+        for row in 1 .. 3 {
+            for col in 1 .. 5 {
+                assert_eq!(table.span_at(row, col), Some((2, 4)));
+            }
+        }
+        assert_eq!(table.span_at(0, 1), None);
+        assert_eq!(table.span_at(1, 0), None);
+        assert_eq!(table.span_at(3, 1), None);
+    }
+
+
+    #[test]
+    fn neighbor_counts() {
+        let table = Table::<()>::empty(3, 4);
+        let coords = |row, col| table.neighbors(row, col).map(|(r, c, _)| (r, c)).collect::<Vec<_>>();
+        assert_eq!(coords(0, 0), vec![(1, 0), (0, 1)]);
+        assert_eq!(coords(2, 3), vec![(1, 3), (2, 2)]);
+        assert_eq!(coords(0, 2), vec![(1, 2), (0, 1), (0, 3)]);
+        assert_eq!(coords(1, 0), vec![(0, 0), (2, 0), (1, 1)]);
+        assert_eq!(coords(1, 2), vec![(0, 2), (2, 2), (1, 1), (1, 3)]);
+        assert_eq!(Table::<()>::empty(1, 1).neighbors(0, 0).count(), 0);
+        assert_eq!(Table::<()>::empty(1, 1).neighbors(u32::MAX, u32::MAX).count(), 0);
+    }
+
+
+    #[test]
+    fn fill_empty_keeps_spans() {
+        let mut table = Table::empty(3, 3);
+        table.set_cell(1, 0, 0, 2, 2).unwrap();
+        table.fill_empty(0);
+        assert_eq!(table.cell_stats(), CellStats { empty: 0, occupied: 6, shadowed: 3 });
+        assert_eq!(table.span_at(1, 1), Some((2, 2)));
+        assert_eq!(table.get_cell_value(0, 0), Some(&1));
+        assert_eq!(table.get_cell_value(2, 2), Some(&0));
+        assert!(table.validate().is_ok());
+    }
+
+
+    #[test]
+    fn swap_rows_clean() {
+        let mut table = Table::empty(3, 3);
+        table.set_cell("wide", 0, 0, 1, 3).unwrap();
+        table.set_cell("x", 2, 1, 1, 1).unwrap();
+        table.swap_rows(0, 2).unwrap();
+        assert_eq!(table.get_cell_value(0, 1), Some(&"x"));
+        assert_eq!(table.span_at(2, 2), Some((1, 3)));
+        assert_eq!(table[(2, 1)], Cell::Shadowed { col: 0, row: 2 });
+        assert!(table.validate().is_ok());
+    }
+
+    #[test]
+    fn swap_rows_rejects_split_span() {
+        let mut table = Table::empty(3, 2);
+        table.set_cell("tall", 0, 1, 2, 1).unwrap();
+        let before = table.clone();
+        assert!(matches!(table.swap_rows(1, 2), Err(Error::SplitSpan { col: 1, row: 0 })));
+        assert!(matches!(table.swap_rows(0, 2), Err(Error::SplitSpan { col: 1, row: 0 })));
+        assert!(matches!(table.swap_rows(0, 3), Err(Error::OutOfBounds { row: 3, col: 0 })));
+        assert_eq!(table, before);
+    }
+
+    #[test]
+    fn swap_columns_clean() {
+        let mut table = Table::empty(3, 3);
+        table.set_cell("tall", 0, 0, 3, 1).unwrap();
+        table.set_cell("x", 1, 2, 1, 1).unwrap();
+        table.set_column_alignment(2, Alignment::Right);
+        table.swap_columns(0, 2).unwrap();
+        assert_eq!(table.get_cell_value(1, 0), Some(&"x"));
+        assert_eq!(table.span_at(2, 2), Some((3, 1)));
+        assert_eq!(table[(1, 2)], Cell::Shadowed { col: 2, row: 0 });
+        assert_eq!(table.column_alignment(0), Alignment::Right);
+        assert!(table.validate().is_ok());
+    }
+
+    #[test]
+    fn swap_columns_rejects_split_span() {
+        let mut table = Table::empty(2, 3);
+        table.set_cell("wide", 1, 0, 1, 2).unwrap();
+        let before = table.clone();
+        assert!(matches!(table.swap_columns(1, 2), Err(Error::SplitSpan { col: 0, row: 1 })));
+        assert!(matches!(table.swap_columns(0, 2), Err(Error::SplitSpan { col: 0, row: 1 })));
+        assert!(matches!(table.swap_columns(5, 0), Err(Error::OutOfBounds { row: 0, col: 5 })));
+        assert_eq!(table, before);
+    }
+
+
+    fn spanning() -> Table<u32> {
+        let mut table = Table::empty(3, 4);
+        table.set_cell(1, 0, 0, 1, 3).unwrap();
+        table.set_cell(2, 1, 0, 2, 1).unwrap();
+        table.set_cell(3, 1, 2, 2, 2).unwrap();
+        table.set_cell(4, 0, 3, 1, 1).unwrap();
+        table
+    }
+
+    #[test]
+    fn rotate_cw() {
+        let rotated = spanning().rotate_cw();
+        assert_eq!(rotated.size(), (4, 3));
+        assert_eq!(rotated.get_cell_value(0, 0), Some(&2));
+        assert_eq!(rotated.span_at(0, 0), Some((1, 2)));
+        assert_eq!(rotated.get_cell_value(0, 2), Some(&1));
+        assert_eq!(rotated.span_at(0, 2), Some((3, 1)));
+        assert_eq!(rotated.get_cell_value(2, 0), Some(&3));
+        assert_eq!(rotated.get_cell_value(3, 2), Some(&4));
+        assert!(rotated.validate().is_ok());
+    }
+
+    #[test]
+    fn rotate_four_times() {
+        let table = spanning();
+        assert_eq!(table.clone().rotate_cw().rotate_cw().rotate_cw().rotate_cw(), table);
+        assert_eq!(table.clone().rotate_ccw().rotate_ccw().rotate_ccw().rotate_ccw(), table);
+        assert_eq!(table.clone().rotate_cw().rotate_ccw(), table);
+    }
+
+
+    #[test]
+    fn flip_horizontal_keeps_anchor_top_left() {
+        let mut table = spanning();
+        table.flip_horizontal();
+        assert_eq!(table.get_cell_value(0, 1), Some(&1));
+        assert_eq!(table.span_at(0, 3), Some((1, 3)));
+        assert_eq!(table.get_cell_value(0, 0), Some(&4));
+        assert_eq!(table.get_cell_value(1, 0), Some(&3));
+        assert_eq!(table.anchor_of(2, 1), Some((1, 0)));
+        assert_eq!(table.get_cell_value(1, 3), Some(&2));
+        assert!(table.validate().is_ok());
+        table.flip_horizontal();
+        assert_eq!(table, spanning());
+    }
+
+    #[test]
+    fn flip_vertical_keeps_anchor_top_left() {
+        let mut table = spanning();
+        table.flip_vertical();
+        assert_eq!(table.get_cell_value(2, 0), Some(&1));
+        assert_eq!(table.get_cell_value(0, 0), Some(&2));
+        assert_eq!(table.span_at(1, 0), Some((2, 1)));
+        assert_eq!(table.get_cell_value(0, 2), Some(&3));
+        assert_eq!(table.anchor_of(1, 3), Some((0, 2)));
+        assert_eq!(table.get_cell_value(2, 3), Some(&4));
+        assert!(table.validate().is_ok());
+        table.flip_vertical();
+        assert_eq!(table, spanning());
+    }
+
+
+    #[test]
+    fn subtable_clips_spans() {
+        let table = spanning();
+        let sub = table.subtable(1, 1, 2, 2);
+        assert_eq!(sub.size(), (2, 2));
+        // 3 sits at the right edge of the window and loses its second column,
+        // 2 and 1 are anchored outside, so their shadows are not copied
+        assert_eq!(sub.into_rows(), vec![vec![None, Some(3)], vec![None, None]]);
+        let sub = table.subtable(1, 1, 2, 2);
+        assert_eq!(sub.span_at(1, 1), Some((2, 1)));
+        assert_eq!(sub.cell_stats(), CellStats { empty: 2, occupied: 1, shadowed: 1 });
+        assert!(sub.validate().is_ok());
+
+        let sub = table.subtable(0, 0, 1, 2);
+        assert_eq!(sub.span_at(0, 0), Some((1, 2)));
+        assert!(sub.validate().is_ok());
+    }
+
+
+    #[test]
+    fn trim_trailing_rows_and_columns() {
+        let mut table = Table::empty(4, 5);
+        table.set_cell(1, 0, 0, 1, 1).unwrap();
+        table.set_cell(2, 2, 1, 1, 2).unwrap();
+        table.trim();
+        assert_eq!(table.size(), (3, 3));
+        assert_eq!(table.span_at(2, 2), Some((1, 2)));
+        assert!(table.validate().is_ok());
+
+        let mut table = Table::<u8>::empty(2, 2);
+        table.trim();
+        assert_eq!(table.size(), (0, 0));
+
+        let mut table = Table::<u8>::empty(4, 0).transpose();
+        assert_eq!(table.size(), (0, 4));
+        table.trim();
+        assert_eq!(table.size(), (0, 0));
+    }
+
+
+    #[test]
+    fn from_rows_into_rows_round_trip() {
+        let rows = vec![
+            vec![Some('a'), None, Some('b')],
+            vec![None, None, None],
+            vec![Some('c'), Some('d'), None],
+        ];
+        let table = Table::from_rows(rows.clone());
+        assert_eq!(table.size(), (3, 3));
+        assert_eq!(table.into_rows(), rows);
+
+        let padded = Table::from_rows(vec![vec![Some(1)], vec![Some(2), Some(3)]]);
+        assert_eq!(padded.into_rows(), vec![vec![Some(1), None], vec![Some(2), Some(3)]]);
+        assert!(Table::<u8>::from_rows(vec![]).into_rows().is_empty());
+    }
+
+
+    #[test]
+    fn find_only_occupied() {
+        let mut table = Table::empty(3, 3);
+        table.set_cell(5, 0, 1, 2, 2).unwrap();
+        table.set_cell(6, 2, 0, 1, 1).unwrap();
+        table.set_cell(5, 2, 2, 1, 1).unwrap();
+        assert_eq!(table.find(|&n| n == 5), Some((0, 1)));
+        assert_eq!(table.find(|&n| n > 5), Some((2, 0)));
+        assert_eq!(table.find(|&n| n > 6), None);
+        assert_eq!(table.find_all(|&n| n == 5).collect::<Vec<_>>(), vec![(0, 1), (2, 2)]);
+        assert_eq!(table.find_all(|_| true).count(), 3);
+    }
+
+
+    #[test]
+    fn content_bounds_of_span() {
+        let mut table = Table::empty(100, 100);
+        assert_eq!(table.content_bounds(), None);
+        table.set_cell((), 40, 30, 3, 5).unwrap();
+        assert_eq!(table.content_bounds(), Some((40, 30, 42, 34)));
+        table.set_cell((), 10, 60, 1, 1).unwrap();
+        assert_eq!(table.content_bounds(), Some((10, 30, 42, 60)));
+    }
+
+
+    #[test]
+    fn builder_pads_rows() {
+        let mut builder = Table::builder();
+        builder.push_row(vec![Some(1)]);
+        builder.push_row(vec![Some(2), None, Some(3)]);
+        builder.push_row(vec![]);
+        let table = builder.finish();
+        assert_eq!(table.size(), (3, 3));
+        assert_eq!(table.into_rows(), vec![
+            vec![Some(1), None, None],
+            vec![Some(2), None, Some(3)],
+            vec![None, None, None],
+        ]);
+    }
+
+
+    #[test]
+    fn push_row_on_empty_table() {
+        let mut table = Table::new();
+        table.push_row(vec![Some('a'), None]);
+        assert_eq!(table.size(), (1, 2));
+        assert_eq!(table.get_cell_value(0, 0), Some(&'a'));
+    }
+
+    #[test]
+    fn push_row_widens() {
+        let mut table = Table::empty(1, 2);
+        table.set_cell('x', 0, 0, 1, 2).unwrap();
+        table.push_row(vec![Some('a')]);
+        table.push_row(vec![None, None, Some('b')]);
+        assert_eq!(table.size(), (3, 3));
+        assert_eq!(table.span_at(0, 1), Some((1, 2)));
+        assert_eq!(table.into_rows(), vec![
+            vec![Some('x'), None, None],
+            vec![Some('a'), None, None],
+            vec![None, None, Some('b')],
+        ]);
+    }
+
+
+    #[test]
+    fn empties() {
+        let mut table = Table::empty(2, 3);
+        table.set_cell(1, 0, 0, 2, 1).unwrap();
+        table.set_cell(2, 0, 1, 1, 1).unwrap();
+        assert_eq!(table.empties().collect::<Vec<_>>(), vec![(0, 2), (1, 1), (1, 2)]);
+        table.fill_empty(0);
+        assert_eq!(table.empties().count(), 0);
+        assert_eq!(Table::<u8>::new().empties().count(), 0);
+    }
+
+
+    #[test]
+    fn shadows_match_spans() {
+        let table = spanning();
+        let expected: u32 = table.values().map(|cell| cell.rowspan * cell.colspan - 1).sum();
+        assert_eq!(table.shadows().count(), expected as usize);
+        assert!(table.shadows().all(|(row, col, anchor_row, anchor_col)| table.anchor_of(row, col) == Some((anchor_row, anchor_col))));
+        assert_eq!(table.shadows().next(), Some((0, 1, 0, 0)));
+    }
+
+
+    #[test]
+    fn swap_cells_keeps_spans() {
+        let mut table = spanning();
+        table.swap_cells((0, 0), (1, 2)).unwrap();
+        assert_eq!(table.get_cell_value(0, 0), Some(&3));
+        assert_eq!(table.span_at(0, 0), Some((1, 3)));
+        assert_eq!(table.get_cell_value(1, 2), Some(&1));
+        assert_eq!(table.span_at(1, 2), Some((2, 2)));
+        assert!(table.validate().is_ok());
+
+        table.swap_cells((1, 0), (1, 0)).unwrap();
+        assert!(matches!(table.swap_cells((0, 0), (0, 1)), Err(Error::Shadowed { col: 0, row: 0 })));
+        assert!(matches!(table.swap_cells((0, 0), (3, 0)), Err(Error::OutOfBounds { row: 3, col: 0 })));
+        let mut table = Table::empty(1, 2);
+        table.set_cell(1, 0, 0, 1, 1).unwrap();
+        assert!(matches!(table.swap_cells((0, 0), (0, 1)), Err(Error::Empty { col: 1, row: 0 })));
+    }
+
+
+    #[test]
+    fn merge_2x2() {
+        let mut table = Table::from_rows(vec![
+            vec![Some(1), Some(2), Some(3)],
+            vec![Some(4), Some(5), Some(6)],
+        ]);
+        table.merge(0, 1, 2, 2).unwrap();
+        assert_eq!(table.span_at(1, 2), Some((2, 2)));
+        assert_eq!(table.get_cell_value(0, 1), Some(&2));
+        assert_eq!(table.values().map(|cell| *cell.value).collect::<Vec<_>>(), vec![1, 2, 4]);
+        assert!(table.validate().is_ok());
+    }
+
+    #[test]
+    fn merge_rejects_other_spans() {
+        let mut table = Table::from_rows(vec![vec![Some(1), Some(2), Some(3)]; 3]);
+        table.merge(1, 1, 2, 2).unwrap();
+        let before = table.clone();
+        assert!(matches!(table.merge(0, 0, 2, 2), Err(Error::Overlap { col: 1, row: 1 })));
+        assert!(matches!(table.merge(0, 2, 3, 1), Err(Error::Overlap { col: 2, row: 1 })));
+        assert!(matches!(table.merge(2, 2, 1, 1), Err(Error::Shadowed { col: 1, row: 1 })));
+        assert!(matches!(table.merge(2, 0, 2, 1), Err(Error::OutOfBounds { row: 2, col: 0 })));
+        assert!(matches!(table.merge(0, 0, u32::MAX, 1), Err(Error::OutOfBounds { row: 0, col: 0 })));
+        assert!(matches!(table.merge(0, 0, 0, 1), Err(Error::InvalidSpan { .. })));
+        assert_eq!(table, before);
+
+        // growing an existing span over 1x1 cells is allowed
+        table.merge(1, 1, 2, 2).unwrap();
+        table.merge(0, 0, 3, 1).unwrap();
+        assert!(table.validate().is_ok());
+    }
+
+
+    #[test]
+    fn unmerge_3x2() {
+        let mut table = Table::empty(4, 3);
+        table.set_cell('a', 1, 0, 3, 2).unwrap();
+        table.unmerge(3, 1).unwrap();
+        assert_eq!(table.span_at(1, 0), Some((1, 1)));
+        assert_eq!(table.get_cell_value(1, 0), Some(&'a'));
+        for (row, col) in [(1, 1), (2, 0), (2, 1), (3, 0), (3, 1)] {
+            assert_eq!(table[(row, col)], Cell::Empty);
+        }
+        assert_eq!(table.shadows().count(), 0);
+        assert!(matches!(table.unmerge(0, 0), Err(Error::Empty { col: 0, row: 0 })));
+        assert!(matches!(table.unmerge(4, 0), Err(Error::OutOfBounds { row: 4, col: 0 })));
+    }
+
+
+    #[test]
+    fn try_set_value_bounds() {
+        let mut table = Table::empty(2, 2);
+        assert_eq!(table.try_set_value(1, 1, 'a').unwrap(), None);
+        assert_eq!(table.try_set_value(1, 1, 'b').unwrap(), Some('a'));
+        assert!(matches!(table.try_set_value(2, 0, 'c'), Err(Error::OutOfBounds { row: 2, col: 0 })));
+        assert!(matches!(table.try_set_value(0, 2, 'c'), Err(Error::OutOfBounds { row: 0, col: 2 })));
+        assert!(matches!(table.try_set_value(u32::MAX, u32::MAX, 'c'), Err(Error::OutOfBounds { .. })));
+        assert_eq!(table.size(), (2, 2));
+    }
+
+
+    #[test]
+    fn format_tsv_2x2() {
+        let mut table = Table::empty(2, 2);
+        table.set_cell(1, 0, 0, 1, 1).unwrap();
+        table.set_cell(2, 0, 1, 2, 1).unwrap();
+        table.set_cell(3, 1, 0, 1, 1).unwrap();
+        let mut out = String::new();
+        table.format_tsv(&mut out, |w, n| write!(w, "{n}")).unwrap();
+        assert_eq!(out, "1\t2\n3\t\n");
+    }
+
+
+    #[cfg(feature="csv")]
+    #[test]
+    fn from_csv_reader_pads() {
+        let data = "a,b,c\n\"x,y\",,z\nlast\n";
+        let mut reader = csv::ReaderBuilder::new().has_headers(false).flexible(true).from_reader(data.as_bytes());
+        let table = Table::from_csv_reader(&mut reader).unwrap();
+        assert_eq!(table.size(), (3, 3));
+        let s = |s: &str| Some(s.to_string());
+        assert_eq!(table.into_rows(), vec![
+            vec![s("a"), s("b"), s("c")],
+            vec![s("x,y"), None, s("z")],
+            vec![s("last"), None, None],
+        ]);
+    }
+
+
+    #[test]
+    fn display_i32() {
+        let mut table = Table::empty(2, 2);
+        table.set_cell(-1, 0, 0, 1, 1).unwrap();
+        table.set_cell(200, 0, 1, 2, 1).unwrap();
+        table.set_cell(3, 1, 0, 1, 1).unwrap();
+        assert_eq!(table.to_string(), "\
+┌────┬─────┐
+│ -1 │ 200 │
+├────┤     │
+│ 3  │     │
+└────┴─────┘
+");
+    }
+
+
+    #[test]
+    fn cells_by_column_order() {
+        let table = Table::from_rows(vec![vec![Some(0), Some(1), Some(2)], vec![Some(10), Some(11), Some(12)]]);
+        let order: Vec<_> = table.cells_by_column().map(|(row, col, cell)| {
+            assert_eq!(*cell, Cell::Occupied { value: row * 10 + col, colspan: 1, rowspan: 1 });
+            (row, col)
+        }).collect();
+        assert_eq!(order, vec![(0, 0), (1, 0), (0, 1), (1, 1), (0, 2), (1, 2)]);
+        assert_eq!(Table::<i32>::empty(0, 3).cells_by_column().count(), 0);
+    }
+
+
+    #[test]
+    fn retain_clears_removed_span() {
+        let mut table = Table::empty(3, 3);
+        table.set_cell(1, 0, 0, 2, 2).unwrap();
+        table.set_cell(2, 0, 2, 1, 1).unwrap();
+        table.set_cell(3, 2, 0, 1, 1).unwrap();
+        table.retain(|_, col, &n| n != 1 && col == 0);
+        assert_eq!(table.into_rows(), vec![vec![None; 3], vec![None; 3], vec![Some(3), None, None]]);
+
+        let mut table = Table::empty(2, 2);
+        table.set_cell(1, 0, 0, 2, 2).unwrap();
+        table.set_header(0, 0, true).unwrap();
+        table.retain(|_, _, _| false);
+        assert_eq!(table.cell_stats(), CellStats { empty: 4, occupied: 0, shadowed: 0 });
+        assert!(!table.is_header(0, 0));
+    }
+
+
+    #[test]
+    fn get_or_insert_with() {
+        let mut table = Table::empty(2, 2);
+        table.set_cell(1, 0, 0, 1, 2).unwrap();
+        *table.get_or_insert_with(0, 0, || unreachable!()) += 10;
+        *table.get_or_insert_with(0, 1, || unreachable!()) += 10;
+        assert_eq!(table.get_cell_value(0, 0), Some(&21));
+
+        *table.get_or_insert_with(1, 1, || 5) += 1;
+        assert_eq!(table.get_cell_value(1, 1), Some(&6));
+        *table.get_or_insert_with(3, 0, || 7) += 1;
+        assert_eq!(table.size(), (4, 2));
+        assert_eq!(table.get_cell_value(3, 0), Some(&8));
+    }
+
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn get_or_insert_with_max_coordinate() {
+        let mut table = Table::empty(1, 1);
+        table.get_or_insert_with(u32::MAX, 0, || 1);
+    }
+
+
+    #[cfg(feature="rkyv")]
+    #[test]
+    fn archived_get_cell() {
+        let mut table = Table::empty(2, 3);
+        table.set_cell(7u32, 1, 1, 1, 2).unwrap();
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&table).unwrap();
+        // SAFETY: `bytes` was just produced by `to_bytes` for this exact type
+        let archived = unsafe { rkyv::access_unchecked::<ArchivedTable<u32>>(&bytes) };
+        assert_eq!((archived.num_rows(), archived.num_cols()), (2, 3));
+        match archived.get_cell(1, 1) {
+            Some(ArchivedCell::Occupied { value, colspan, rowspan }) => {
+                assert_eq!((value.to_native(), colspan.to_native(), rowspan.to_native()), (7, 2, 1));
+            }
+            _ => panic!("expected an occupied cell"),
+        }
+        assert!(matches!(archived.get_cell(1, 2), Some(ArchivedCell::Shadowed { .. })));
+        assert!(matches!(archived.get_cell(0, 0), Some(ArchivedCell::Empty)));
+        assert!(archived.get_cell(0, 3).is_none());
+        assert!(archived.get_cell(2, 0).is_none());
+    }
+
+
+    #[test]
+    fn heap_bytes_counts_capacity() {
+        let mut table = Table::<u64>::with_capacity(10, 10);
+        assert_eq!(table.heap_bytes(), 100 * std::mem::size_of::<Cell<u64>>());
+        table.set_cell(1, 0, 0, 1, 1).unwrap();
+        assert_eq!(table.heap_bytes(), 100 * std::mem::size_of::<Cell<u64>>());
+        table.shrink_to_fit();
+        assert_eq!(table.heap_bytes(), std::mem::size_of::<Cell<u64>>());
+    }
+
+    #[cfg(feature="datasize")]
+    #[test]
+    fn total_heap_bytes_includes_values() {
+        let mut table = Table::with_capacity(2, 2);
+        table.set_cell(String::with_capacity(100), 0, 0, 1, 1).unwrap();
+        assert_eq!(table.total_heap_bytes(), table.heap_bytes() + 100);
+    }
+
+
+    #[test]
+    fn append_rows_offsets_shadows() {
+        let mut top = Table::empty(2, 3);
+        top.set_cell('a', 0, 0, 2, 1).unwrap();
+        let mut bottom = Table::empty(3, 2);
+        bottom.set_cell('b', 0, 1, 3, 1).unwrap();
+        bottom.set_cell('c', 2, 0, 1, 1).unwrap();
+        top.append_rows(bottom).unwrap();
+        assert_eq!(top.size(), (5, 3));
+        assert_eq!(top.span_at(3, 1), Some((3, 1)));
+        assert_eq!(top[(3, 1)], Cell::Shadowed { col: 1, row: 2 });
+        assert_eq!(top[(4, 1)], Cell::Shadowed { col: 1, row: 2 });
+        assert_eq!(top.get_cell_value(4, 0), Some(&'c'));
+        assert_eq!(top[(4, 2)], Cell::Empty);
+        assert!(top.validate().is_ok());
+    }
+
+    #[test]
+    fn append_rows_pads_narrower_self() {
+        let mut top = Table::from_rows(vec![vec![Some(1)]]);
+        top.append_rows(Table::from_rows(vec![vec![Some(2), Some(3)]])).unwrap();
+        assert_eq!(top.into_rows(), vec![vec![Some(1), None], vec![Some(2), Some(3)]]);
+    }
+
+
+    #[test]
+    fn append_columns_offsets_shadows() {
+        let mut left = Table::empty(1, 2);
+        left.set_cell('a', 0, 0, 1, 2).unwrap();
+        let mut right = Table::empty(2, 3);
+        right.set_cell('b', 1, 0, 1, 3).unwrap();
+        right.set_column_alignment(1, Alignment::Center);
+        left.append_columns(right).unwrap();
+        assert_eq!(left.size(), (2, 5));
+        assert_eq!(left.span_at(1, 4), Some((1, 3)));
+        assert_eq!(left[(1, 3)], Cell::Shadowed { col: 2, row: 1 });
+        assert_eq!(left[(1, 4)], Cell::Shadowed { col: 2, row: 1 });
+        assert_eq!(left[(1, 0)], Cell::Empty);
+        assert_eq!(left.column_alignment(3), Alignment::Center);
+        assert!(left.validate().is_ok());
+    }
+
+
+    #[test]
+    fn map_with_coords_anchor_positions() {
+        let mut table = Table::empty(3, 3);
+        table.set_cell((), 0, 1, 2, 2).unwrap();
+        table.set_cell((), 2, 0, 1, 1).unwrap();
+        table.set_cell((), 2, 2, 1, 1).unwrap();
+        let mapped = table.map_with_coords(|row, col, ()| (row, col));
+        assert!(mapped.values().all(|cell| *cell.value == (cell.row, cell.col)));
+        assert_eq!(mapped.occupied_count(), 3);
+        assert_eq!(mapped[(1, 2)], Cell::Shadowed { col: 1, row: 0 });
+    }
+
+
+    #[test]
+    fn format_html_styled_empty_cells() {
+        let mut table = Table::empty(1, 3);
+        table.set_cell(1, 0, 1, 1, 1).unwrap();
+        let render = |empty| {
+            let mut out = String::new();
+            table.format_html_styled(&mut out, &empty, |w, n| write!(w, "{n}")).unwrap();
+            out
+        };
+        let body = |cells: &str| format!("<table>\n<tbody>\n<tr>\n{cells}</tr>\n</tbody>\n</table>\n");
+        assert_eq!(render(EmptyCellStyle::Empty), body("<td></td><td>1</td>\n<td></td>"));
+        assert_eq!(render(EmptyCellStyle::Omit), body("<td>1</td>\n"));
+        assert_eq!(render(EmptyCellStyle::Placeholder("&nbsp;".into())), body("<td>&nbsp;</td><td>1</td>\n<td>&nbsp;</td>"));
+    }
+
+
+    #[test]
+    fn count_by_str() {
+        let mut table = Table::empty(2, 3);
+        table.set_cell("ok", 0, 0, 1, 1).unwrap();
+        table.set_cell("fail", 0, 1, 2, 2).unwrap();
+        table.set_cell("ok", 1, 0, 1, 1).unwrap();
+        let counts = table.count_by(|&s| s);
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["ok"], 2);
+        assert_eq!(counts["fail"], 1);
+        assert_eq!(table.count_by(|s| s.len())[&2], 2);
+    }
+
+
+    #[test]
+    fn alignment_survives_map() {
+        let mut table = Table::empty(1, 3);
+        table.set_column_alignment(1, Alignment::Right);
+        table.set_column_alignment(2, Alignment::Center);
+        table.set_column_alignment(2, Alignment::Left);
+        let mapped = table.map(|n: u8| n);
+        assert_eq!(mapped.column_alignment(0), Alignment::Left);
+        assert_eq!(mapped.column_alignment(1), Alignment::Right);
+        assert_eq!(mapped.column_alignment(2), Alignment::Left);
+        assert_eq!(mapped.column_alignment(9), Alignment::Left);
+        assert_eq!(mapped.alignments, vec![(1, Alignment::Right)]);
+    }
+
+    #[test]
+    fn alignment_in_renderers() {
+        let mut table = Table::from_rows(vec![vec![Some("a"), Some("b")], vec![Some("ccc"), Some("ddd")]]);
+        table.set_column_alignment(1, Alignment::Right);
+        let mut out = String::new();
+        table.format_box(&mut out, |w, s| w.write_str(s)).unwrap();
+        assert!(out.contains("│ a   │   b │"));
+
+        let mut out = String::new();
+        table.format_html_attrs(&mut out, |w, s| w.write_str(s), |_, col, _| if col == 1 { "class=\"x\" style=\"color: red\"".into() } else { String::new() }).unwrap();
+        assert!(out.contains("<td class=\"x\" style=\"text-align: right; color: red\">b</td>"));
+        assert_eq!(out.matches("style=").count(), 2);
+        let mut out = String::new();
+        table.format_html(&mut out, |w, s| w.write_str(s)).unwrap();
+        assert!(out.contains("<td style=\"text-align: right\">b</td>"));
+        assert!(out.contains("<td>a</td>"));
+    }
+
+    #[test]
+    fn append_rows_takes_over_alignments() {
+        let mut top = Table::<u8>::empty(1, 3);
+        top.set_column_alignment(0, Alignment::Center);
+        let mut bottom = Table::empty(1, 3);
+        bottom.set_column_alignment(0, Alignment::Right);
+        bottom.set_column_alignment(2, Alignment::Right);
+        top.append_rows(bottom).unwrap();
+        assert_eq!(top.column_alignment(0), Alignment::Center);
+        assert_eq!(top.column_alignment(1), Alignment::Left);
+        assert_eq!(top.column_alignment(2), Alignment::Right);
+    }
+
+    #[cfg(feature="serde")]
+    #[test]
+    fn alignment_serde() {
+        let mut table = Table::from_rows(vec![vec![Some(1), Some(2)]]);
+        table.set_column_alignment(1, Alignment::Center);
+        let json = serde_json::to_string(&table).unwrap();
+        assert_eq!(serde_json::from_str::<Table<i32>>(&json).unwrap(), table);
+        // tables saved before alignments existed still load
+        let old = r#"{"num_cols":1,"num_rows":1,"cells":[{"Occupied":{"value":1,"colspan":1,"rowspan":1}}]}"#;
+        assert_eq!(serde_json::from_str::<Table<i32>>(old).unwrap(), Table::from_rows(vec![vec![Some(1)]]));
+    }
+
+    #[cfg(feature="serde")]
+    #[test]
+    fn alignment_bincode() {
+        let plain = Table::<i32>::empty(1, 1);
+        let bytes = bincode::serialize(&plain).unwrap();
+        assert_eq!(bincode::deserialize::<Table<i32>>(&bytes).unwrap(), plain);
+
+        let mut table = Table::from_rows(vec![vec![Some(1), Some(2)]]);
+        table.set_column_alignment(1, Alignment::Right);
+        let bytes = bincode::serialize(&table).unwrap();
+        assert_eq!(bincode::deserialize::<Table<i32>>(&bytes).unwrap(), table);
+    }
+
+
+    #[test]
+    fn visual_cells_sequence() {
+        let mut table = Table::empty(2, 3);
+        table.set_cell('a', 0, 0, 2, 2).unwrap();
+        table.set_cell('b', 1, 2, 1, 1).unwrap();
+        assert_eq!(table.visual_cells().collect::<Vec<_>>(), vec![
+            VisualCell::Occupied { value: &'a', row: 0, col: 0, rowspan: 2, colspan: 2 },
+            VisualCell::Empty { row: 0, col: 2 },
+            VisualCell::Occupied { value: &'b', row: 1, col: 2, rowspan: 1, colspan: 1 },
+        ]);
+    }
+
+
+    #[test]
+    fn clone_map_leaves_source() {
+        let table = spanning();
+        let before = table.clone();
+        let doubled = table.clone_map(|n| n * 2);
+        assert_eq!(table, before);
+        assert_eq!(doubled, before.map(|n| n * 2));
+    }
+
+
+    #[test]
+    fn fill_region_over_span() {
+        let mut table = Table::empty(3, 3);
+        table.set_cell(9, 0, 0, 2, 2).unwrap();
+        table.fill_region(1, 1, 1, 3, 0).unwrap();
+        assert_eq!(table.size(), (3, 4));
+        assert!(!table.contains(&9));
+        assert_eq!(table.shadows().count(), 0);
+        assert_eq!(table.clone().into_rows(), vec![
+            vec![None; 4],
+            vec![None, Some(0), Some(0), Some(0)],
+            vec![None; 4],
+        ]);
+        assert!(matches!(table.fill_region(u32::MAX, 0, 2, 1, 0), Err(Error::OutOfBounds { row: u32::MAX, col: 0 })));
+        assert!(matches!(table.fill_region(0, u32::MAX, 1, 1, 0), Err(Error::OutOfBounds { row: 0, col: u32::MAX })));
+        assert!(table.fill_region(0, 0, 0, 5, 0).is_ok());
+        assert_eq!(table.size(), (3, 4));
+    }
+
+
+    #[test]
+    fn cell_at_hits_anchor() {
+        let mut table = Table::empty(3, 3);
+        table.set_cell('a', 1, 1, 2, 2).unwrap();
+        let cell = table.cell_at(2, 2).unwrap();
+        assert_eq!((*cell.value, cell.row, cell.col, cell.rowspan, cell.colspan), ('a', 1, 1, 2, 2));
+        assert_eq!(table.cell_at(1, 1).map(|cell| (cell.row, cell.col)), Some((1, 1)));
+        assert!(table.cell_at(0, 0).is_none());
+        assert!(table.cell_at(3, 3).is_none());
+    }
+
+
+    #[cfg(feature="serde")]
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Sparse(#[serde(with="crate::serde_sparse")] Table<String>);
+
+    #[cfg(feature="serde")]
+    #[test]
+    fn serde_sparse_round_trip() {
+        let mut table = Table::empty(1000, 1000);
+        table.set_cell("a".to_string(), 0, 0, 1, 1).unwrap();
+        table.set_cell("b".to_string(), 500, 400, 3, 2).unwrap();
+        table.set_cell("c".to_string(), 999, 999, 1, 1).unwrap();
+        let json = serde_json::to_string(&Sparse(table.clone())).unwrap();
+        assert_eq!(json, r#"{"rows":1000,"cols":1000,"cells":[[0,0,"a",1,1],[500,400,"b",3,2],[999,999,"c",1,1]]}"#);
+        let Sparse(read) = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, table);
+    }
+
+    #[cfg(feature="serde")]
+    #[test]
+    fn serde_sparse_rejects_bad_cells() {
+        let read = |json: &str| serde_json::from_str::<Sparse>(json).map(|Sparse(table)| table);
+        assert!(read(r#"{"rows":2,"cols":2,"cells":[[1,1,"a",2,1]]}"#).is_err());
+        assert!(read(r#"{"rows":2,"cols":2,"cells":[[0,0,"a",4294967295,1]]}"#).is_err());
+        assert!(read(r#"{"rows":2,"cols":2,"cells":[[0,0,"a",1,4294967295]]}"#).is_err());
+        assert!(read(r#"{"rows":2,"cols":2,"cells":[[0,0,"a",2,2],[1,1,"b",1,1]]}"#).is_err());
+        assert!(read(r#"{"rows":2,"cols":2,"cells":[[0,0,"a",0,1]]}"#).is_err());
+        assert!(read(r#"{"rows":4294967295,"cols":4294967295,"cells":[]}"#).is_err());
+        assert!(read(r#"{"rows":2,"cols":2,"cells":[[0,0,"a",2,2]]}"#).unwrap().validate().is_ok());
+    }
+
+
+    #[test]
+    fn iter_visits_every_coordinate() {
+        let table = spanning();
+        assert_eq!(table.iter().count(), 12);
+        assert_eq!(table.iter().filter(|(_, _, value)| value.is_some()).count(), table.occupied_count());
+        assert_eq!(table.iter().nth(5), Some((1, 1, None)));
+        assert_eq!(table.iter().nth(6), Some((1, 2, Some(&3))));
+        assert_eq!(Table::<u8>::empty(0, 5).iter().count(), 0);
+    }
+
+
+    #[test]
+    fn area_checks_product() {
+        assert_eq!(area(3, 4), Some(12));
+        assert_eq!(area(0, u32::MAX), Some(0));
+        #[cfg(target_pointer_width="64")]
+        assert_eq!(area(u32::MAX, u32::MAX), Some(u32::MAX as usize * u32::MAX as usize));
+        #[cfg(target_pointer_width="32")]
+        assert_eq!(area(70_000, 70_000), None);
+    }
+
+    #[cfg(target_pointer_width="32")]
+    #[test]
+    fn set_cell_too_large() {
+        let mut table = Table::empty(1, 1);
+        assert!(matches!(table.set_cell(1, 0, 0, 70_000, 70_000), Err(Error::TooLarge { rows: 70_000, cols: 70_000 })));
+        assert_eq!(table.size(), (1, 1));
+    }
+
+    #[test]
+    fn span_arithmetic_overflow() {
+        let mut table = Table::empty(2, 2);
+        table.set_cell(1, 1, 1, 1, 1).unwrap();
+        let before = table.clone();
+        assert!(matches!(table.set_cell(2, 1, 0, u32::MAX, 1), Err(Error::OutOfBounds { row: 1, col: 0 })));
+        assert!(matches!(table.set_cell(2, 0, 1, 1, u32::MAX), Err(Error::OutOfBounds { row: 0, col: 1 })));
+        assert!(matches!(table.try_set_cell_in_bounds(2, 1, 0, u32::MAX, 1), Err(Error::OutOfBounds { .. })));
+        assert!(matches!(table.try_set_cell_in_bounds(2, 0, 1, 1, u32::MAX), Err(Error::OutOfBounds { .. })));
+        assert!(matches!(table.set_cell_checked(2, 1, 0, u32::MAX, 1), Err(Error::OutOfBounds { .. })));
+        assert!(matches!(table.set_cell_checked(2, 0, 1, 1, u32::MAX), Err(Error::OutOfBounds { .. })));
+        assert!(matches!(table.merge(1, 1, 1, u32::MAX), Err(Error::OutOfBounds { .. })));
+        assert_eq!(table, before);
+    }
+
+
+    #[test]
+    fn zero_span_rejected() {
+        let mut table = Table::empty(2, 2);
+        table.set_cell(1, 0, 0, 1, 1).unwrap();
+        let before = table.clone();
+        assert!(matches!(table.set_cell(2, 0, 0, 0, 1), Err(Error::InvalidSpan { col: 0, row: 0 })));
+        assert!(matches!(table.set_cell(2, 1, 1, 1, 0), Err(Error::InvalidSpan { col: 1, row: 1 })));
+        assert!(matches!(table.set_cell(2, 5, 5, 0, 0), Err(Error::InvalidSpan { .. })));
+        assert!(matches!(table.try_set_cell_in_bounds(2, 0, 0, 0, 1), Err(Error::InvalidSpan { .. })));
+        assert!(matches!(table.set_cell_checked(2, 0, 0, 1, 0), Err(Error::InvalidSpan { .. })));
+        assert_eq!(table, before);
+        assert!(table.validate().is_ok());
+    }
+
+
+    #[test]
+    fn rows_nested() {
+        let table = Table::from_rows(vec![vec![Some(1), None], vec![None, Some(2)], vec![Some(3), Some(4)]]);
+        assert_eq!(table.rows().count(), 3);
+        let occupied: Vec<usize> = table.rows().map(|row| row.filter(|cell| cell.is_occupied()).count()).collect();
+        assert_eq!(occupied, vec![1, 1, 2]);
+        assert!(table.rows().all(|row| row.count() == 2));
+        assert_eq!(Table::<u8>::new().rows().count(), 0);
+    }
+
+
+    #[test]
+    fn row_values_anchor_row_only() {
+        let mut table = Table::empty(2, 3);
+        table.set_cell('a', 0, 0, 2, 1).unwrap();
+        table.set_cell('b', 0, 1, 1, 1).unwrap();
+        table.set_cell('c', 1, 2, 1, 1).unwrap();
+        let values = |row| table.row_values(row).unwrap().map(|cell| (*cell.value, cell.row, cell.col)).collect::<Vec<_>>();
+        assert_eq!(values(0), vec![('a', 0, 0), ('b', 0, 1)]);
+        assert_eq!(values(1), vec![('c', 1, 2)]);
+        assert!(table.row_values(2).is_none());
+    }
+
+
+    #[test]
+    fn header_cell_renders_th() {
+        let mut table = Table::from_rows(vec![vec![Some("name"), Some("x")], vec![Some("a"), Some("1")]]);
+        table.set_header(1, 0, true).unwrap();
+        let mut out = String::new();
+        table.format_html(&mut out, |w, s| w.write_str(s)).unwrap();
+        assert_eq!(out.matches("<th>").count(), 1);
+        assert!(out.contains("<th>a</th>"));
+        table.set_header(1, 0, false).unwrap();
+        assert!(!table.is_header(1, 0));
+        assert!(matches!(table.set_header(2, 0, true), Err(Error::OutOfBounds { row: 2, col: 0 })));
+    }
+
+    #[test]
+    fn header_follows_anchor() {
+        let mut table = Table::empty(2, 2);
+        table.set_cell(1, 0, 0, 1, 2).unwrap();
+        table.set_cell(2, 1, 1, 1, 1).unwrap();
+        table.set_header(0, 0, true).unwrap();
+        table.set_header(1, 1, true).unwrap();
+        assert!(matches!(table.set_header(0, 1, true), Err(Error::Shadowed { col: 0, row: 0 })));
+        assert!(matches!(table.set_header(1, 0, true), Err(Error::Empty { col: 0, row: 1 })));
+        table.insert_row(0);
+        table.insert_column(0);
+        assert!(table.is_header(1, 1) && table.is_header(2, 2));
+        table.remove_cell(1, 2);
+        assert!(!table.is_header(1, 1));
+        table.set_cell(3, 2, 0, 1, 3).unwrap();
+        assert!(!table.is_header(2, 2));
+        assert!(table.headers.is_empty());
+    }
+
+    #[test]
+    fn delete_column_drops_header_of_removed_anchor() {
+        let mut table = Table::empty(1, 3);
+        table.set_cell(1, 0, 1, 1, 1).unwrap();
+        table.set_cell(2, 0, 2, 1, 1).unwrap();
+        table.set_header(0, 1, true).unwrap();
+        table.delete_column(1);
+        assert_eq!(table.get_cell_value(0, 1), Some(&2));
+        assert!(!table.is_header(0, 1));
+
+        // a spanning header moves with its anchor
+        let mut table = Table::empty(2, 2);
+        table.set_cell(1, 0, 0, 2, 2).unwrap();
+        table.set_header(0, 0, true).unwrap();
+        table.delete_column(0);
+        table.delete_row(0);
+        assert!(table.is_header(0, 0));
+    }
+
+    #[cfg(feature="serde")]
+    #[test]
+    fn header_serde() {
+        let mut table = Table::from_rows(vec![vec![Some(1), Some(2)]]);
+        table.set_header(0, 1, true).unwrap();
+        let json = serde_json::to_string(&table).unwrap();
+        assert!(serde_json::from_str::<Table<i32>>(&json).unwrap().is_header(0, 1));
+        // tables saved before header flags existed still load
+        let old = r#"{"num_cols":1,"num_rows":1,"cells":[{"Occupied":{"value":1,"colspan":1,"rowspan":1}}],"alignments":[]}"#;
+        assert_eq!(serde_json::from_str::<Table<i32>>(old).unwrap(), Table::from_rows(vec![vec![Some(1)]]));
+    }
+
+
+    #[cfg(feature="serde")]
+    #[test]
+    fn header_bincode() {
+        let mut table = Table::from_rows(vec![vec![Some(1), Some(2)]]);
+        table.set_header(0, 1, true).unwrap();
+        let bytes = bincode::serialize(&table).unwrap();
+        assert_eq!(bincode::deserialize::<Table<i32>>(&bytes).unwrap(), table);
+    }
+
+
+    #[test]
+    fn reserve_grows_capacity() {
+        let mut table = Table::<u8>::empty(2, 2);
+        table.reserve(3, 2);
+        assert!(table.cells.capacity() >= 20);
+        assert_eq!(table.size(), (2, 2));
+        let capacity = table.cells.capacity();
+        table.set_cell(1, 4, 3, 1, 1).unwrap();
+        assert_eq!(table.cells.capacity(), capacity);
+    }
+
+
+    #[test]
+    fn visually_eq_different_edits() {
+        let mut a = Table::empty(2, 3);
+        a.set_cell('x', 0, 0, 2, 2).unwrap();
+        a.set_cell('y', 0, 2, 1, 1).unwrap();
+
+        let mut b = Table::from_rows(vec![vec![Some('x'), Some('q'), Some('y')], vec![Some('r'), None, None]]);
+        b.merge(0, 0, 2, 2).unwrap();
+        b.set_column_alignment(2, Alignment::Right);
+        b.set_header(0, 2, true).unwrap();
+        assert!(a.visually_eq(&b));
+        assert_ne!(a, b);
+
+        // a stray shadow is not visible either
+        b[(1, 2)] = Cell::Shadowed { col: 2, row: 0 };
+        assert!(a.visually_eq(&b));
+
+        let mut c = a.clone();
+        c.set_cell('z', 1, 2, 1, 1).unwrap();
+        assert!(!a.visually_eq(&c));
+        assert!(!a.visually_eq(&Table::empty(2, 4)));
+    }
+
+
+    #[test]
+    fn compact_repairs_corruption() {
+        let mut table = Table::empty(3, 3);
+        table.set_cell(1, 0, 0, 2, 2).unwrap();
+        table[(0, 1)] = Cell::Empty;
+        table[(2, 2)] = Cell::Shadowed { col: 0, row: 0 };
+        table[(2, 0)] = Cell::Occupied { value: 2, rowspan: 1, colspan: 9 };
+        assert!(table.validate().is_err());
+        table.compact();
+        assert!(table.validate().is_ok());
+        assert_eq!(table.span_at(1, 1), Some((2, 2)));
+        assert_eq!(table.span_at(2, 2), Some((1, 3)));
+
+        // overlapping spans: the later anchor keeps its span, the earlier one shrinks
+        let mut table = Table::empty(2, 2);
+        table[(0, 0)] = Cell::Occupied { value: 1, rowspan: 2, colspan: 2 };
+        table[(1, 1)] = Cell::Occupied { value: 2, rowspan: 1, colspan: 1 };
+        table[(0, 1)] = Cell::Occupied { value: 3, rowspan: 2, colspan: 1 };
+        table.compact();
+        assert!(table.validate().is_ok());
+        assert_eq!(table.span_at(0, 0), Some((1, 1)));
+        assert_eq!(table.occupied_count(), 3);
+    }
+
+
+    #[test]
+    fn debug_grid() {
+        let mut table = Table::empty(2, 3);
+        table.set_cell(10, 0, 0, 2, 1).unwrap();
+        table.set_cell(2, 0, 1, 1, 2).unwrap();
+        assert_eq!(format!("{table:?}"), "\
+Table 2x3 [
+    10     2 ^(0,1)
+    ^(0,0) . .
+]");
+        table.set_column_alignment(1, Alignment::Center);
+        table.set_header(0, 0, true).unwrap();
+        assert!(format!("{table:?}").ends_with("] alignments: [(1, Center)] headers: [(0, 0)]"));
+        assert_eq!(format!("{:?}", Table::<u8>::new()), "Table 0x0 [\n]");
+    }
+
+
+    #[test]
+    fn into_values_owned() {
+        let mut table = Table::empty(3, 3);
+        table.set_cell("a".to_string(), 0, 1, 2, 2).unwrap();
+        table.set_cell("b".to_string(), 2, 0, 1, 1).unwrap();
+        let values: Vec<_> = table.into_values().collect();
+        assert_eq!(values, vec![(0, 1, "a".to_string(), 2, 2), (2, 0, "b".to_string(), 1, 1)]);
+        assert_eq!(Table::<u8>::empty(2, 2).into_values().count(), 0);
+    }
+
+
+    #[test]
+    fn grow_to_each_dimension() {
+        let mut table = Table::from_rows(vec![vec![Some(1), Some(2)]]);
+        table.grow_to(1, 3);
+        assert_eq!(table.into_rows(), vec![vec![Some(1), Some(2), None]]);
+
+        let mut table = Table::from_rows(vec![vec![Some(1), Some(2)]]);
+        table.grow_to(2, 1);
+        assert_eq!(table.into_rows(), vec![vec![Some(1), Some(2)], vec![None, None]]);
+
+        let mut table = Table::empty(2, 2);
+        table.set_cell(1, 0, 0, 2, 2).unwrap();
+        table.grow_to(3, 4);
+        table.grow_to(1, 1);
+        assert_eq!(table.size(), (3, 4));
+        assert_eq!(table.span_at(1, 1), Some((2, 2)));
+        assert!(table.validate().is_ok());
+    }
+
+
+    #[test]
+    fn get_value_mut_errors() {
+        let mut table = Table::empty(2, 2);
+        table.set_cell(1, 0, 0, 1, 2).unwrap();
+        *table.get_value_mut(0, 0).unwrap() += 1;
+        assert_eq!(table.get_cell_value(0, 0), Some(&2));
+        assert_eq!(table.get_value_mut(0, 1), Err(CellError::Shadowed { anchor: (0, 0) }));
+        assert_eq!(table.get_value_mut(1, 0), Err(CellError::Empty));
+        assert_eq!(table.get_value_mut(2, 0), Err(CellError::OutOfBounds));
+        assert_eq!(table.get_value_mut(0, 2), Err(CellError::OutOfBounds));
+    }
+
+
+    #[test]
+    fn contains_values_only() {
+        let mut table = Table::empty(2, 2);
+        table.set_cell(Cell::Empty, 0, 0, 2, 1).unwrap();
+        table.set_cell(Cell::Occupied { value: 1, colspan: 1, rowspan: 1 }, 0, 1, 1, 1).unwrap();
+        assert!(table.contains(&Cell::Empty));
+        assert!(table.contains(&Cell::Occupied { value: 1, colspan: 1, rowspan: 1 }));
+        // the table's own empty and shadowed coordinates are never matched
+        assert!(!table.contains(&Cell::Shadowed { col: 0, row: 0 }));
+        table.remove_cell(0, 0);
+        assert!(!table.contains(&Cell::Empty));
+    }
+
+
+    #[test]
+    fn update_through_shadow() {
+        let mut table = Table::empty(3, 3);
+        table.set_cell(1, 0, 0, 2, 2).unwrap();
+        table.update(1, 1, |n| *n += 10).unwrap();
+        table.update(0, 0, |n| *n *= 2).unwrap();
+        assert_eq!(table.get_cell_value(0, 0), Some(&22));
+        assert_eq!(table.update(2, 2, |_| unreachable!()), Err(CellError::Empty));
+        assert_eq!(table.update(3, 0, |_| unreachable!()), Err(CellError::OutOfBounds));
+    }
+
+
+    #[test]
+    fn write_to_vec() {
+        let mut table = Table::empty(1, 2);
+        table.set_cell("a,b".to_string(), 0, 0, 1, 1).unwrap();
+        table.set_cell("c".to_string(), 0, 1, 1, 1).unwrap();
+
+        let mut html = Vec::new();
+        table.write_html(&mut html, |w, s| w.write_str(s)).unwrap();
+        let mut expected = String::new();
+        table.format_html(&mut expected, |w, s| w.write_str(s)).unwrap();
+        assert_eq!(String::from_utf8(html).unwrap(), expected);
+
+        let mut csv = Vec::new();
+        table.write_csv(&mut csv, |w, s| w.write_str(s)).unwrap();
+        assert_eq!(csv, b"\"a,b\",c\n");
+    }
+
+    #[test]
+    fn write_reports_io_error() {
+        struct Full;
+        impl io::Write for Full {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::StorageFull, "full"))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let table = Table::from_rows(vec![vec![Some(1)]]);
+        let err = table.write_csv(Full, |w, n| write!(w, "{n}")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::StorageFull);
+    }
+
+
+    #[test]
+    fn shadow_rows_are_not_empty() {
+        let mut table = Table::empty(3, 3);
+        table.set_cell(1, 0, 0, 2, 2).unwrap();
+        assert!(!table.row_is_empty(0));
+        assert!(!table.row_is_empty(1));
+        assert!(table.row_is_empty(2));
+        assert!(!table.column_is_empty(1));
+        assert!(table.column_is_empty(2));
+        assert!(table.row_is_empty(3) && table.column_is_empty(3));
+        assert!(Table::<i32>::empty(0, 3).column_is_empty(2));
+    }
+
+
+    #[test]
+    fn paginate_clips_spans() {
+        let mut table = Table::empty(5, 2);
+        table.set_cell('a', 1, 0, 3, 1).unwrap();
+        table.set_cell('b', 4, 1, 1, 1).unwrap();
+        let pages: Vec<_> = table.paginate_rows(2).collect();
+        assert_eq!(pages.iter().map(Table::size).collect::<Vec<_>>(), vec![(2, 2), (2, 2), (1, 2)]);
+        assert_eq!(pages[0].span_at(1, 0), Some((1, 1)));
+        assert_eq!(pages[1].cell_stats(), CellStats { empty: 4, occupied: 0, shadowed: 0 });
+        assert_eq!(pages[2].get_cell_value(0, 1), Some(&'b'));
+        assert!(pages.iter().all(|page| page.validate().is_ok()));
+        assert_eq!(Table::<u8>::new().paginate_rows(3).count(), 0);
+    }
+
+
+    #[test]
+    #[should_panic(expected = "rows_per_page must not be zero")]
+    fn paginate_zero_rows_per_page() {
+        let _ = Table::<u8>::empty(2, 2).paginate_rows(0);
+    }
+
+
+    #[test]
+    fn cell_stats_sum() {
+        let table = spanning();
+        let stats = table.cell_stats();
+        assert_eq!(stats, CellStats { empty: 2, occupied: 4, shadowed: 6 });
+        assert_eq!(stats.empty + stats.occupied + stats.shadowed, (table.num_rows() * table.num_cols()) as usize);
+        assert_eq!(Table::<u8>::new().cell_stats(), CellStats::default());
+    }
+
+
+    #[test]
+    fn format_html_with_tags_default() {
+        let table = Table::from_rows(vec![vec![Some(1), Some(2)], vec![Some(3), None]]);
+        let (mut tagged, mut plain) = (String::new(), String::new());
+        table.format_html_with_tags(&mut tagged, &HtmlTags::default(), 0, |w, n| write!(w, "{n}")).unwrap();
+        table.format_html(&mut plain, |w, n| write!(w, "{n}")).unwrap();
+        assert_eq!(tagged, plain);
+    }
+
+    #[test]
+    fn format_html_with_custom_tags() {
+        let mut table = Table::empty(2, 2);
+        table.set_cell(1, 0, 0, 1, 2).unwrap();
+        table.set_cell(2, 1, 0, 1, 1).unwrap();
+        let tags = HtmlTags {
+            table: None,
+            thead: Some("header".into()),
+            tbody: None,
+            tr: "div".into(),
+            td: "span".into(),
+            th: "b".into(),
+        };
+        let mut out = String::new();
+        table.format_html_with_tags(&mut out, &tags, 1, |w, n| write!(w, "{n}")).unwrap();
+        assert_eq!(out, "\
+<header>
+<div>
+<b colspan=2>1</b>
+</div>
+</header>
+<div>
+<span>2</span>
+<span></span></div>
+");
+    }
+
+
+    #[test]
+    fn last_row_and_col() {
+        assert_eq!(Table::<u8>::new().last_row(), None);
+        assert_eq!(Table::<u8>::new().last_col(), None);
+        let table = Table::<u8>::empty(0, 3);
+        assert_eq!((table.last_row(), table.last_col()), (None, Some(2)));
+        let table = Table::<u8>::empty(4, 1);
+        assert_eq!((table.last_row(), table.last_col()), (Some(3), Some(0)));
+    }
+
+
+    #[test]
+    fn map_in_place_keeps_layout() {
+        let mut table = spanning();
+        let capacity = table.cells.capacity();
+        table.map_in_place(|n| *n *= 10);
+        assert_eq!(table.cells.capacity(), capacity);
+        assert_eq!(table, spanning().map(|n| n * 10));
+        assert_eq!(table.shadows().collect::<Vec<_>>(), spanning().shadows().collect::<Vec<_>>());
+    }
+
+
+    #[cfg(feature="json")]
+    #[test]
+    fn to_json_value_rows() {
+        let mut table = Table::empty(2, 3);
+        table.set_cell("a", 0, 0, 2, 1).unwrap();
+        table.set_cell("b", 0, 1, 1, 2).unwrap();
+        table.set_cell("c", 1, 2, 1, 1).unwrap();
+        assert_eq!(table.to_json_value().unwrap(), serde_json::json!([["a", "b", null], [null, null, "c"]]));
+        assert_eq!(Table::<u8>::new().to_json_value().unwrap(), serde_json::json!([]));
+    }
+
+
+    #[cfg(feature="serde")]
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Grid(#[serde(with="crate::serde_grid")] Table<i32>);
+
+    #[cfg(feature="serde")]
+    #[test]
+    fn serde_grid_round_trip() {
+        let table = spanning().map(|n| n as i32);
+        let json = serde_json::to_string(&Grid(table.clone())).unwrap();
+        assert_eq!(json, r#"[[{"value":1,"rowspan":1,"colspan":3},null,null,4],[{"value":2,"rowspan":2,"colspan":1},null,{"value":3,"rowspan":2,"colspan":2},null],[null,null,null,null]]"#);
+        let Grid(read) = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, table);
+        let Grid(plain) = serde_json::from_str("[[1],[null,2]]").unwrap();
+        assert_eq!(plain.into_rows(), vec![vec![Some(1), None], vec![None, Some(2)]]);
+    }
+
+    #[cfg(feature="serde")]
+    #[test]
+    fn serde_grid_rejects_overlap() {
+        let read = |json: &str| serde_json::from_str::<Grid>(json).map(|Grid(table)| table);
+        assert!(read(r#"[[{"value":1,"rowspan":2,"colspan":2},2],[null,null]]"#).is_err());
+        assert!(read(r#"[[1,{"value":2,"rowspan":2,"colspan":1}],[{"value":3,"rowspan":1,"colspan":2},null]]"#).is_err());
+        assert!(read(r#"[[{"value":1,"rowspan":3,"colspan":1}],[null]]"#).is_err());
+        assert!(read(r#"[[{"value":1,"rowspan":4294967295,"colspan":1}]]"#).is_err());
+    }
+
+    #[cfg(feature="serde")]
+    #[test]
+    fn serde_grid_rejects_huge_grid() {
+        // 70000x70000 cells can not be allocated, reading must fail instead of aborting
+        let mut json = format!("[[{}0]", "0,".repeat(69_999));
+        json.push_str(&",[]".repeat(69_999));
+        json.push(']');
+        assert!(serde_json::from_str::<Grid>(&json).is_err());
+    }
+
+    #[cfg(feature="serde")]
+    #[test]
+    fn serde_grid_map_values() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Values(#[serde(with="crate::serde_grid")] Table<serde_json::Value>);
+
+        let mut table = Table::empty(1, 2);
+        table.set_cell(serde_json::json!({ "value": 1, "rowspan": 1, "colspan": 1, "note": "x" }), 0, 0, 1, 1).unwrap();
+        table.set_cell(serde_json::json!({ "value": 2 }), 0, 1, 1, 1).unwrap();
+        let json = serde_json::to_string(&Values(table.clone())).unwrap();
+        let Values(read) = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, table);
+    }
+
+
+    #[test]
+    fn get_cell_value_bounds() {
+        let mut table = Table::empty(2, 2);
+        table.set_cell(1, 0, 0, 1, 2).unwrap();
+        assert_eq!(table.get_cell_value(0, 0), Some(&1));
+        assert_eq!(table.get_cell_value(0, 1), None);
+        assert_eq!(table.get_cell_value(1, 0), None);
+        // (0, 2) would alias (1, 0) without the column check
+        table.set_cell(2, 1, 0, 1, 1).unwrap();
+        assert_eq!(table.get_cell_value(0, 2), None);
+        assert_eq!(table.get_cell_value(2, 0), None);
+        assert_eq!(table.get_cell_value_mut(0, 2), None);
     }
-}
-pub struct CellValue<'a, T> {
-    pub value: &'a T,
-    pub col: u32,
-    pub row: u32,
-    pub colspan: u32,
-    pub rowspan: u32,
 }
@@ -0,0 +1,60 @@
+//! Serde helpers representing a [`Table`] as a dense grid of rows, for use with
+//! `#[serde(with = "table::serde_grid")]`.
+//!
+//! Every row is a list with one entry per column. Empty and shadowed coordinates are `null`,
+//! 1x1 cells are the plain value and spanning cells are `{ "value": .., "rowspan": .., "colspan": .. }`.
+//! A table without spans is therefore just a `Vec<Vec<Option<T>>>`.
+//!
+//! Values and spans round-trip exactly, as long as no value itself serializes to a map with
+//! exactly the keys `value`, `rowspan` and `colspan`; such a value is read back as a spanning cell.
+//! The shadow bookkeeping is not stored, it is rebuilt from the spans on read.
+//! Overlapping spans, spans reaching past the grid and grids too large to allocate are rejected.
+
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use crate::{Table, Cell};
+
+// maps with keys besides these three are plain values
+#[derive(Serialize, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+enum GridCell<T> {
+    Spanning { value: T, rowspan: u32, colspan: u32 },
+    Plain(T),
+}
+
+pub fn serialize<T: Serialize, S: Serializer>(table: &Table<T>, serializer: S) -> Result<S::Ok, S::Error> {
+    let rows: Vec<Vec<Option<GridCell<&T>>>> = (0 .. table.num_rows).map(|row| {
+        table.row(row).unwrap().iter().map(|cell| match *cell {
+            Cell::Occupied { ref value, rowspan: 1, colspan: 1 } => Some(GridCell::Plain(value)),
+            Cell::Occupied { ref value, rowspan, colspan } => Some(GridCell::Spanning { value, rowspan, colspan }),
+            Cell::Empty | Cell::Shadowed { .. } => None,
+        }).collect()
+    }).collect();
+    rows.serialize(serializer)
+}
+
+pub fn deserialize<'de, T: Deserialize<'de>, D: Deserializer<'de>>(deserializer: D) -> Result<Table<T>, D::Error> {
+    use serde::de::Error;
+
+    let rows: Vec<Vec<Option<GridCell<T>>>> = Vec::deserialize(deserializer)?;
+    let num_cols = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let (Ok(num_rows), Ok(num_cols)) = (u32::try_from(rows.len()), u32::try_from(num_cols)) else {
+        let (rows, cols) = (rows.len().min(u32::MAX as usize) as u32, num_cols.min(u32::MAX as usize) as u32);
+        return Err(D::Error::custom(crate::Error::TooLarge { rows, cols }));
+    };
+    let mut table = Table::try_empty(num_rows, num_cols).map_err(D::Error::custom)?;
+    for (row, cells) in rows.into_iter().enumerate() {
+        for (col, cell) in cells.into_iter().enumerate() {
+            let (value, rowspan, colspan) = match cell {
+                Some(GridCell::Plain(value)) => (value, 1, 1),
+                Some(GridCell::Spanning { value, rowspan, colspan }) => (value, rowspan, colspan),
+                None => continue,
+            };
+            let (row, col) = (row as u32, col as u32);
+            if !table.span_in_bounds(row, col, rowspan, colspan) {
+                return Err(D::Error::custom(crate::Error::OutOfBounds { row, col }));
+            }
+            table.set_cell_checked(value, row, col, rowspan, colspan).map_err(D::Error::custom)?;
+        }
+    }
+    Ok(table)
+}
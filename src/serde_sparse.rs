@@ -0,0 +1,41 @@
+//! Serde helpers representing a [`Table`] as a list of its occupied cells, for use with
+//! `#[serde(with = "table::serde_sparse")]`.
+//!
+//! The table is written as `{ "rows": R, "cols": C, "cells": [[row, col, value, rowspan, colspan], ..] }`
+//! with one entry per anchor in reading order. Empty and shadowed coordinates are not stored,
+//! so the size depends on the number of occupied cells rather than on the dimensions.
+//!
+//! The shadows are rebuilt from the spans on read. Cells outside `rows` x `cols`
+//! and overlapping spans are rejected, as are dimensions too large to allocate.
+
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use crate::{Table, Error};
+
+#[derive(Serialize, Deserialize)]
+struct SparseTable<T> {
+    rows: u32,
+    cols: u32,
+    cells: Vec<(u32, u32, T, u32, u32)>,
+}
+
+pub fn serialize<T: Serialize, S: Serializer>(table: &Table<T>, serializer: S) -> Result<S::Ok, S::Error> {
+    SparseTable {
+        rows: table.num_rows,
+        cols: table.num_cols,
+        cells: table.values().map(|cell| (cell.row, cell.col, cell.value, cell.rowspan, cell.colspan)).collect(),
+    }.serialize(serializer)
+}
+
+pub fn deserialize<'de, T: Deserialize<'de>, D: Deserializer<'de>>(deserializer: D) -> Result<Table<T>, D::Error> {
+    use serde::de::Error as _;
+
+    let sparse: SparseTable<T> = SparseTable::deserialize(deserializer)?;
+    let mut table = Table::try_empty(sparse.rows, sparse.cols).map_err(D::Error::custom)?;
+    for (row, col, value, rowspan, colspan) in sparse.cells {
+        if !table.span_in_bounds(row, col, rowspan, colspan) {
+            return Err(D::Error::custom(Error::OutOfBounds { row, col }));
+        }
+        table.set_cell_checked(value, row, col, rowspan, colspan).map_err(D::Error::custom)?;
+    }
+    Ok(table)
+}